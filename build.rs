@@ -0,0 +1,7 @@
+fn main() {
+    // the target triple selects the prebuilt artifacts bote can install
+    println!(
+        "cargo:rustc-env=BOTE_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+}
@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::path::{Component, Path};
+
+use lzma::{LzmaReader, LzmaWriter};
+use tar::{Archive, Builder};
+
+use crate::blob::BlobClient;
+use crate::error::Error;
+use crate::library::record::BinaryArtifact;
+
+/// TARGET is the target triple bote was built for. Only artifacts of this target are installed.
+pub const TARGET: &str = env!("BOTE_TARGET");
+
+/// COMPRESSION_PRESET is the xz preset that is used to pack artifacts.
+const COMPRESSION_PRESET: u32 = 6;
+
+/// pack() packs an install prefix into an xz compressed tar archive. Symlinks are kept as they
+/// are.
+pub fn pack(prefix: &Path, archive: &Path) -> Result<(), anyhow::Error> {
+    let writer = LzmaWriter::new_compressor(File::create(archive)?, COMPRESSION_PRESET)?;
+    let mut builder = Builder::new(writer);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(".", prefix)?;
+
+    builder.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// unpack() unpacks an archive created by pack() into an install prefix. Every entry is checked
+/// before it is unpacked and an error is returned if its path or the target of a link leads
+/// outside of the prefix, so an artifact can't write anywhere else.
+pub fn unpack(archive: &Path, prefix: &Path) -> Result<(), anyhow::Error> {
+    let reader = LzmaReader::new_decompressor(File::open(archive)?)?;
+
+    for entry in Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let link = entry.link_name()?.map(|link| link.into_owned());
+        let hard_link = entry.header().entry_type().is_hard_link();
+
+        check_entry(&path, link.as_deref(), hard_link)?;
+        if !entry.unpack_in(prefix)? {
+            return Err(unsafe_entry(&path).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// check_entry() makes sure that an entry of an archive stays inside of the install prefix.
+/// Symlinks are resolved relative to the directory of the entry and hard links relative to the
+/// prefix, like tar does.
+fn check_entry(path: &Path, link: Option<&Path>, hard_link: bool) -> Result<(), Error> {
+    let depth = depth_inside(0, path).ok_or_else(|| unsafe_entry(path))?;
+
+    if let Some(link) = link {
+        let base = if hard_link {
+            0
+        } else {
+            depth.saturating_sub(1)
+        };
+        depth_inside(base, link).ok_or_else(|| unsafe_entry(path))?;
+    }
+
+    Ok(())
+}
+
+/// depth_inside() returns how many directories below the prefix a relative path leads if it
+/// starts base directories below the prefix. None is returned if the path is absolute or leaves
+/// the prefix at any point.
+fn depth_inside(base: usize, path: &Path) -> Option<usize> {
+    let mut depth = base;
+
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => depth = depth.checked_sub(1)?,
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(depth)
+}
+
+/// unsafe_entry() returns the error for an entry that would leave the install prefix.
+fn unsafe_entry(path: &Path) -> Error {
+    Error::UnsafeArtifactEntry {
+        entry: path.display().to_string(),
+    }
+}
+
+/// upload() packs an install prefix and uploads it as an artifact for the target of this
+/// machine.
+pub async fn upload(blobs: &BlobClient, prefix: &Path) -> Result<BinaryArtifact, anyhow::Error> {
+    let archive = tempfile::NamedTempFile::new()?;
+    pack(prefix, archive.path())?;
+
+    Ok(BinaryArtifact {
+        target: TARGET.to_string(),
+        blob: blobs.upload(archive.path()).await?,
+        hash: blobs.hash_file(archive.path())?,
+        size: archive.as_file().metadata()?.len(),
+    })
+}
+
+/// download() downloads an artifact and unpacks it into an install prefix. The artifact has to
+/// match the hash and size that were published with it.
+pub async fn download(
+    blobs: &BlobClient,
+    artifact: &BinaryArtifact,
+    prefix: &Path,
+) -> Result<(), anyhow::Error> {
+    let directory = tempfile::tempdir()?;
+    let archive = directory.path().join("artifact.tar.xz");
+
+    blobs
+        .download_verified(&artifact.blob, &artifact.hash, artifact.size, &archive)
+        .await?;
+    unpack(&archive, prefix)?;
+
+    directory.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_entry_accepts_entries_inside_of_the_prefix() {
+        assert!(check_entry(Path::new("./bin/bote"), None, false).is_ok());
+        assert!(check_entry(Path::new("bin/bote"), Some(Path::new("../lib/bote")), false).is_ok());
+        assert!(check_entry(Path::new("lib/x"), Some(Path::new("./lib/y")), true).is_ok());
+    }
+
+    #[test]
+    fn check_entry_rejects_escaping_paths() {
+        assert!(check_entry(Path::new("/etc/passwd"), None, false).is_err());
+        assert!(check_entry(Path::new("bin/../../x"), None, false).is_err());
+    }
+
+    #[test]
+    fn check_entry_rejects_escaping_links() {
+        assert!(check_entry(Path::new("bin/sh"), Some(Path::new("/bin/sh")), false).is_err());
+        assert!(check_entry(Path::new("bin/x"), Some(Path::new("../../x")), false).is_err());
+        assert!(check_entry(Path::new("bin/x"), Some(Path::new("../x")), true).is_err());
+    }
+
+    #[test]
+    fn unpack_restores_a_packed_prefix() {
+        let prefix = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(prefix.path().join("bin")).unwrap();
+        std::fs::create_dir_all(prefix.path().join("lib")).unwrap();
+        std::fs::write(prefix.path().join("bin/tool"), "#!/bin/sh\n").unwrap();
+        std::fs::write(prefix.path().join("lib/libtool.so"), "library").unwrap();
+        std::os::unix::fs::symlink("../lib/libtool.so", prefix.path().join("bin/libtool.so"))
+            .unwrap();
+
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("artifact.tar.xz");
+        pack(prefix.path(), &archive).unwrap();
+
+        let unpacked = tempfile::tempdir().unwrap();
+        unpack(&archive, unpacked.path()).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(unpacked.path().join("bin/tool")).unwrap(),
+            "#!/bin/sh\n"
+        );
+        assert_eq!(
+            std::fs::read_link(unpacked.path().join("bin/libtool.so")).unwrap(),
+            Path::new("../lib/libtool.so")
+        );
+        assert_eq!(
+            std::fs::read_to_string(unpacked.path().join("bin/libtool.so")).unwrap(),
+            "library"
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_a_packed_symlink_outside_of_the_prefix() {
+        let prefix = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", prefix.path().join("passwd")).unwrap();
+
+        let directory = tempfile::tempdir().unwrap();
+        let archive = directory.path().join("artifact.tar.xz");
+        pack(prefix.path(), &archive).unwrap();
+
+        let unpacked = tempfile::tempdir().unwrap();
+        let error = unpack(&archive, unpacked.path()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::UnsafeArtifactEntry { .. })
+        ));
+        assert!(!unpacked.path().join("passwd").exists());
+    }
+}
@@ -0,0 +1,322 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use veilid_core::{
+    CryptoSystem, CryptoSystemVersion, DHTSchema, RoutingContext, TypedKey, ValueData, ValueSubkey,
+};
+
+use crate::error::Error;
+use crate::library::record::{decode, encode, get_value, parse_key};
+use crate::node::VeilidNode;
+
+/// CHUNK_SIZE is the maximum size of a chunk. Every chunk fills a whole subkey.
+pub const CHUNK_SIZE: usize = ValueData::MAX_LEN;
+
+/// CHUNKS_PER_RECORD is the number of chunks stored in a single chunk record. veilid limits the
+/// data of a record to 1 MiB.
+pub const CHUNKS_PER_RECORD: usize = 30;
+
+/// HASHES_PER_SUBKEY is the number of chunk hashes stored in a single subkey of the manifest.
+pub const HASHES_PER_SUBKEY: usize = 512;
+
+/// HASH_SUBKEY_COUNT is the number of manifest subkeys that contain chunk hashes. They follow the
+/// header in subkey 0.
+pub const HASH_SUBKEY_COUNT: usize = 30;
+
+/// MAX_CHUNKS is the maximum number of chunks of a blob.
+pub const MAX_CHUNKS: usize = HASHES_PER_SUBKEY * HASH_SUBKEY_COUNT;
+
+/// The header of a blob manifest, stored in subkey 0 of the manifest record. The key of the
+/// manifest record is the key of the blob.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BlobHeader {
+    /// The size of the blob in bytes.
+    pub size: u64,
+    /// The hash of the whole blob.
+    pub hash: String,
+    /// The number of chunks.
+    pub chunk_count: usize,
+    /// The DHT keys of the records that contain the chunks, CHUNKS_PER_RECORD chunks each.
+    pub records: Vec<String>,
+}
+
+/// A part of the chunk hashes of a blob, stored in the subkeys after the header.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct ChunkHashes {
+    hashes: Vec<String>,
+}
+
+/// A client for the blob store that uploads files into DHT records and downloads them again.
+/// Every chunk is addressed by its position and verified by its hash.
+#[derive(Clone)]
+pub struct BlobClient {
+    routing_context: RoutingContext,
+    crypto: CryptoSystemVersion,
+}
+
+impl BlobClient {
+    /// new() creates a blob client. The node is attached to the network for this.
+    pub async fn new(node: &mut VeilidNode) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            routing_context: node.routing_context().await?,
+            crypto: node.crypto().await?,
+        })
+    }
+
+    /// upload() splits a file into chunks, stores them in chunk records and writes the manifest
+    /// record. The DHT key of the manifest is returned as the key of the blob.
+    pub async fn upload(&self, path: &Path) -> Result<String, anyhow::Error> {
+        let size = fs::metadata(path)?.len();
+        let chunk_count = (size as usize).div_ceil(CHUNK_SIZE);
+        if chunk_count > MAX_CHUNKS {
+            return Err(Error::BlobTooLarge {
+                size,
+                max: (MAX_CHUNKS * CHUNK_SIZE) as u64,
+            }
+            .into());
+        }
+
+        let mut file = File::open(path)?;
+        let mut records = Vec::new();
+        let mut hashes = Vec::new();
+        let mut chunk = vec![0; CHUNK_SIZE];
+
+        for first_chunk in (0..chunk_count).step_by(CHUNKS_PER_RECORD) {
+            let chunks = CHUNKS_PER_RECORD.min(chunk_count - first_chunk);
+            let descriptor = self
+                .routing_context
+                .create_dht_record(DHTSchema::dflt(chunks as u16), None)
+                .await?;
+            let key = *descriptor.key();
+            debug!("Created chunk record {}", key);
+
+            let result = async {
+                for subkey in 0..chunks {
+                    let length = read_chunk(&mut file, &mut chunk)?;
+                    let data = chunk[..length].to_vec();
+                    hashes.push(self.crypto.generate_hash(&data).to_string());
+                    self.set_value(key, subkey as ValueSubkey, data).await?;
+                }
+                Ok::<(), anyhow::Error>(())
+            }
+            .await;
+            self.routing_context.close_dht_record(key).await?;
+            result?;
+
+            records.push(key.to_string());
+            info!(
+                "Uploaded {} of {} chunks",
+                first_chunk + chunks,
+                chunk_count
+            );
+        }
+
+        let header = BlobHeader {
+            size,
+            hash: self.hash_file(path)?,
+            chunk_count,
+            records,
+        };
+
+        let hash_subkeys = hashes.chunks(HASHES_PER_SUBKEY).count();
+        let descriptor = self
+            .routing_context
+            .create_dht_record(DHTSchema::dflt(1 + hash_subkeys as u16), None)
+            .await?;
+        let key = *descriptor.key();
+
+        let result = async {
+            self.set_value(key, 0, encode(&header)?).await?;
+            for (position, hashes) in hashes.chunks(HASHES_PER_SUBKEY).enumerate() {
+                let hashes = ChunkHashes {
+                    hashes: hashes.to_vec(),
+                };
+                self.set_value(key, 1 + position as ValueSubkey, encode(&hashes)?)
+                    .await?;
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        self.routing_context.close_dht_record(key).await?;
+        result?;
+
+        Ok(key.to_string())
+    }
+
+    /// download() downloads a blob into a file. Every chunk is verified against its hash in the
+    /// manifest and the whole file against the hash of the blob. The file is only created if the
+    /// download succeeded.
+    pub async fn download(&self, blob: &str, path: &Path) -> Result<(), anyhow::Error> {
+        let (header, hashes) = self.read_manifest(blob).await?;
+        self.download_chunks(blob, &header, &hashes, path).await
+    }
+
+    /// download_verified() downloads a blob like download(), but only if its manifest matches the
+    /// expected hash and size. This is used for blobs whose hash was published somewhere else.
+    pub async fn download_verified(
+        &self,
+        blob: &str,
+        hash: &str,
+        size: u64,
+        path: &Path,
+    ) -> Result<(), anyhow::Error> {
+        let (header, hashes) = self.read_manifest(blob).await?;
+        if header.hash != hash || header.size != size {
+            return Err(Error::ChecksumMismatch {
+                what: format!("blob {}", blob),
+            }
+            .into());
+        }
+
+        self.download_chunks(blob, &header, &hashes, path).await
+    }
+
+    /// hash_file() returns the hash of a file as it is stored in blob manifests.
+    pub fn hash_file(&self, path: &Path) -> Result<String, anyhow::Error> {
+        Ok(self
+            .crypto
+            .generate_hash_reader(&mut File::open(path)?)?
+            .to_string())
+    }
+
+    /// download_chunks() downloads and verifies all chunks of a blob into a file.
+    async fn download_chunks(
+        &self,
+        blob: &str,
+        header: &BlobHeader,
+        hashes: &[String],
+        path: &Path,
+    ) -> Result<(), anyhow::Error> {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => std::env::current_dir()?,
+        };
+        fs::create_dir_all(&directory)?;
+        let mut file = tempfile::NamedTempFile::new_in(directory)?;
+
+        for (position, record) in header.records.iter().enumerate() {
+            let key = parse_key(record)?;
+            let first_chunk = position * CHUNKS_PER_RECORD;
+            let chunks = CHUNKS_PER_RECORD.min(header.chunk_count - first_chunk);
+
+            self.routing_context.open_dht_record(key, None).await?;
+            let result = async {
+                for subkey in 0..chunks {
+                    let value =
+                        get_value(&self.routing_context, key, subkey as ValueSubkey).await?;
+                    let hash = self.crypto.generate_hash(value.data()).to_string();
+
+                    if hash != hashes[first_chunk + subkey] {
+                        return Err(Error::ChecksumMismatch {
+                            what: format!("chunk {} of blob {}", first_chunk + subkey, blob),
+                        }
+                        .into());
+                    }
+                    file.write_all(value.data())?;
+                }
+                Ok::<(), anyhow::Error>(())
+            }
+            .await;
+            self.routing_context.close_dht_record(key).await?;
+            result?;
+
+            info!(
+                "Downloaded {} of {} chunks",
+                first_chunk + chunks,
+                header.chunk_count
+            );
+        }
+
+        file.flush()?;
+        let size = file.as_file().metadata()?.len();
+        let hash = self.hash_file(file.path())?;
+        if size != header.size || hash != header.hash {
+            return Err(Error::ChecksumMismatch {
+                what: format!("blob {}", blob),
+            }
+            .into());
+        }
+
+        file.persist(path)?;
+
+        Ok(())
+    }
+
+    /// read_manifest() reads the header and all chunk hashes of a blob and checks that they are
+    /// consistent.
+    async fn read_manifest(&self, blob: &str) -> Result<(BlobHeader, Vec<String>), anyhow::Error> {
+        let key = parse_key(blob)?;
+        self.routing_context.open_dht_record(key, None).await?;
+
+        let result = async {
+            let header: BlobHeader =
+                decode(get_value(&self.routing_context, key, 0).await?.data())?;
+
+            let mut hashes = Vec::new();
+            for position in 0..header.chunk_count.div_ceil(HASHES_PER_SUBKEY) {
+                let value =
+                    get_value(&self.routing_context, key, 1 + position as ValueSubkey).await?;
+                hashes.extend(decode::<ChunkHashes>(value.data())?.hashes);
+            }
+
+            Ok::<_, anyhow::Error>((header, hashes))
+        }
+        .await;
+        self.routing_context.close_dht_record(key).await?;
+        let (header, hashes) = result?;
+
+        if hashes.len() != header.chunk_count
+            || header.records.len() != header.chunk_count.div_ceil(CHUNKS_PER_RECORD)
+            || header.chunk_count > MAX_CHUNKS
+        {
+            return Err(Error::InvalidBlob {
+                blob: blob.to_string(),
+            }
+            .into());
+        }
+
+        Ok((header, hashes))
+    }
+
+    /// set_value() writes a subkey of an opened record that was created by this client. Nobody
+    /// else can write the record, so an error is returned if the network holds a newer value.
+    async fn set_value(
+        &self,
+        key: TypedKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        if self
+            .routing_context
+            .set_dht_value(key, subkey, data)
+            .await?
+            .is_some()
+        {
+            return Err(Error::BlobConflict {
+                record: key.to_string(),
+                subkey,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// read_chunk() reads the next chunk of a file into the buffer and returns its length. Only the
+/// last chunk of a file is shorter than the buffer.
+fn read_chunk(file: &mut File, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+    let mut length = 0;
+
+    while length < buffer.len() {
+        match file.read(&mut buffer[length..])? {
+            0 => break,
+            read => length += read,
+        }
+    }
+
+    Ok(length)
+}
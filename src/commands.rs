@@ -1,8 +1,19 @@
+pub mod blob;
+pub mod config;
+pub mod generations;
+pub mod hold;
+pub mod info;
 pub mod init;
 pub mod install;
 pub mod library;
+pub mod list;
+pub mod pin;
 pub mod pride;
 pub mod publish;
+pub mod rollback;
 pub mod search;
+pub mod status;
+pub mod unhold;
 pub mod uninstall;
+pub mod unpin;
 pub mod upgrade;
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::blob::BlobClient;
+use crate::node::VeilidNode;
+use crate::{human, output};
+
+#[derive(Subcommand)]
+pub enum BlobCommands {
+    #[command(about = "Upload a file, e.g. the sources of a package")]
+    Upload {
+        #[arg(help = "The file to upload")]
+        file: PathBuf,
+    },
+    #[command(about = "Download a file and verify it")]
+    Download {
+        #[arg(help = "The key of the blob")]
+        blob: String,
+        #[arg(help = "The path to download the file to")]
+        path: PathBuf,
+    },
+}
+
+/// The result of the blob subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct BlobOutput {
+    /// The key of the uploaded or downloaded blob.
+    pub blob: String,
+    /// The path of the uploaded file or the downloaded file.
+    pub path: PathBuf,
+}
+
+/// run() runs the blob subcommand which stores large files on the veilid DHT, so build scripts
+/// can download them with download_veilid().
+pub async fn run(command: BlobCommands, node: &mut VeilidNode) -> Result<(), anyhow::Error> {
+    let blobs = BlobClient::new(node).await?;
+
+    match command {
+        BlobCommands::Upload { file } => {
+            let blob = blobs.upload(&file).await?;
+            output::set_result(&BlobOutput {
+                blob: blob.clone(),
+                path: file.clone(),
+            })?;
+
+            human!("Uploaded {}.", file.display());
+            human!("Download it in build scripts with:");
+            human!("  download_veilid(\"{}\", \"<path>\");", blob);
+        }
+        BlobCommands::Download { blob, path } => {
+            blobs.download(&blob, &path).await?;
+            output::set_result(&BlobOutput {
+                blob: blob.clone(),
+                path: path.clone(),
+            })?;
+            human!("Downloaded {} to {}.", blob, path.display());
+        }
+    }
+
+    Ok(())
+}
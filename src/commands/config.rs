@@ -0,0 +1,75 @@
+use clap::Subcommand;
+
+use crate::settings;
+use crate::{human, output};
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    #[command(about = "Print the value of a setting")]
+    Get {
+        #[arg(help = "The key of the setting, e.g. keep_versions or veilid.network.upnp")]
+        key: String,
+    },
+    #[command(about = "Change a setting in the configuration file")]
+    Set {
+        #[arg(help = "The key of the setting, e.g. keep_versions or veilid.network.upnp")]
+        key: String,
+        #[arg(help = "The new value as JSON (plain strings don't need quotes)")]
+        value: String,
+    },
+    #[command(about = "List all settings")]
+    List {
+        #[arg(long, help = "Show where each value comes from")]
+        show_origin: bool,
+    },
+    #[command(about = "Reset a setting or the whole configuration to the defaults")]
+    Reset {
+        #[arg(help = "The key of the setting (resets everything if omitted)")]
+        key: Option<String>,
+    },
+}
+
+/// run() runs the config subcommand which is used to inspect and edit the settings of bote. The
+/// result in the JSON output is the changed or requested setting as settings::Entry, or all
+/// settings for list and a reset of the whole configuration.
+pub fn run(command: ConfigCommands) -> Result<(), anyhow::Error> {
+    match command {
+        ConfigCommands::Get { key } => {
+            let entry = settings::get(&key)?;
+            output::set_result(&entry)?;
+            human!("{}", entry.value);
+        }
+        ConfigCommands::Set { key, value } => {
+            let value = settings::set(&key, &value)?;
+            output::set_result(&settings::get(&key)?)?;
+            human!("{} = {}", key, value);
+        }
+        ConfigCommands::List { show_origin } => {
+            let entries = settings::entries()?;
+            output::set_result(&entries)?;
+            for entry in entries {
+                if show_origin {
+                    human!("{:<8} {} = {}", entry.origin, entry.key, entry.value);
+                } else {
+                    human!("{} = {}", entry.key, entry.value);
+                }
+            }
+        }
+        ConfigCommands::Reset { key } => {
+            settings::reset(key.as_deref())?;
+            match key {
+                Some(key) => {
+                    let entry = settings::get(&key)?;
+                    output::set_result(&entry)?;
+                    human!("{} = {}", key, entry.value);
+                }
+                None => {
+                    output::set_result(&settings::entries()?)?;
+                    human!("Reset all settings to their defaults");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
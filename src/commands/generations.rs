@@ -0,0 +1,111 @@
+use clap::Subcommand;
+use serde::Serialize;
+
+use crate::database::InstalledDatabase;
+use crate::profile::{self, Generation};
+use crate::{human, output};
+
+#[derive(Subcommand)]
+pub enum GenerationsCommands {
+    #[command(about = "List all generations of the profile")]
+    List,
+    #[command(about = "Switch to another generation")]
+    Switch {
+        #[arg(help = "The number of the generation")]
+        number: u64,
+    },
+    #[command(about = "Delete generations")]
+    Delete {
+        #[arg(required = true, help = "The numbers of the generations")]
+        numbers: Vec<u64>,
+    },
+}
+
+/// A generation in the JSON output of generations list.
+#[derive(Serialize, Debug)]
+pub struct ListedGeneration {
+    /// The generation.
+    #[serde(flatten)]
+    pub generation: Generation,
+    /// Whether the generation is the active one.
+    pub current: bool,
+}
+
+/// run() runs the generations subcommand which manages the generations of the profile.
+pub fn run(command: GenerationsCommands) -> Result<(), anyhow::Error> {
+    match command {
+        GenerationsCommands::List => list(),
+        GenerationsCommands::Switch { number } => switch(number),
+        GenerationsCommands::Delete { numbers } => delete(numbers),
+    }
+}
+
+/// list() prints all generations and marks the active one.
+fn list() -> Result<(), anyhow::Error> {
+    let current = profile::current_generation()?;
+    let generations = profile::generations()?;
+
+    output::set_result(
+        &generations
+            .iter()
+            .map(|generation| ListedGeneration {
+                generation: generation.clone(),
+                current: current == Some(generation.number),
+            })
+            .collect::<Vec<_>>(),
+    )?;
+
+    for generation in generations {
+        let created_at =
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(generation.created_at);
+        let packages: Vec<String> = generation
+            .packages
+            .iter()
+            .map(|package| format!("{} {}", package.name, package.version))
+            .collect();
+
+        human!(
+            "{:>4} {} {}{}",
+            generation.number,
+            humantime::format_rfc3339_seconds(created_at),
+            packages.join(", "),
+            if current == Some(generation.number) {
+                " (current)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// switch() switches the profile to another generation and updates the installed package
+/// database to match it. The generation is the result in the JSON output.
+fn switch(number: u64) -> Result<(), anyhow::Error> {
+    let generation = profile::switch(number)?;
+    output::set_result(&generation)?;
+
+    let mut database = InstalledDatabase::load()?;
+    database.set_packages(generation.packages);
+    database.save()?;
+
+    human!("Switched to generation {}", number);
+
+    Ok(())
+}
+
+/// delete() deletes generations that aren't active. The numbers of the deleted generations are
+/// the result in the JSON output.
+fn delete(numbers: Vec<u64>) -> Result<(), anyhow::Error> {
+    let mut deleted = Vec::new();
+
+    for number in numbers {
+        profile::delete(number)?;
+        deleted.push(number);
+        output::set_result(&deleted)?;
+        human!("Deleted generation {}", number);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use log::warn;
+use serde::Serialize;
+
+use crate::database::InstalledDatabase;
+use crate::{human, output};
+
+/// The result of the hold and unhold subcommands in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct HoldOutput {
+    /// The name of the package.
+    pub package: String,
+    /// Whether the package is held now.
+    pub held: bool,
+}
+
+/// run() runs the hold subcommand which prevents an installed package from being upgraded.
+pub fn run(package: String) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    database.get_or_not_found(&package)?;
+    output::set_result(&HoldOutput {
+        package: package.clone(),
+        held: true,
+    })?;
+
+    if !database.hold(&package) {
+        warn!("{} is already held", package);
+        return Ok(());
+    }
+
+    database.save()?;
+    human!("{} is now held and won't be upgraded", package);
+
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::database::{InstalledDatabase, InstalledPackage};
+use crate::error::Error;
+use crate::library::record::{LibraryHeader, PackageMetadata, PublishedVersion};
+use crate::library::{self, sync};
+use crate::node::VeilidNode;
+use crate::package::PackageReference;
+use crate::registry::{parse_package_address, Registry};
+use crate::store;
+use crate::version::Version;
+use crate::{human, output};
+
+/// The result of the info subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct PackageInfo {
+    /// The name of the package.
+    pub package: String,
+    /// The DHT key of the library.
+    pub library: String,
+    /// The alias of the library, if it is imported.
+    pub library_alias: Option<String>,
+    /// The version whose build script is synced, or the latest version.
+    pub version: String,
+    /// The metadata of that version.
+    pub metadata: PackageMetadata,
+    /// The signature of that version.
+    pub signature: SignatureStatus,
+    /// All published versions, newest first.
+    pub versions: Vec<VersionInfo>,
+    /// The installed package, if it is installed from the library.
+    pub installed: Option<InstalledInfo>,
+    /// The build script, if it was requested.
+    pub script: Option<String>,
+}
+
+/// The signature status of a package version.
+#[derive(Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// The signature is valid. owner is set if the signer owns the library.
+    Valid { signer: String, owner: bool },
+    /// The signature is missing or invalid.
+    Invalid { reason: String },
+    /// The build script isn't synced, so the signature can't be verified.
+    Unknown,
+}
+
+/// A published version of a package.
+#[derive(Serialize, Debug)]
+pub struct VersionInfo {
+    /// The version.
+    pub version: String,
+    /// The time of publishing in seconds since the unix epoch.
+    pub published_at: u64,
+    /// The targets prebuilt artifacts are available for.
+    pub prebuilt: Vec<String>,
+}
+
+/// An installed package and the files of its store path.
+#[derive(Serialize, Debug)]
+pub struct InstalledInfo {
+    /// The database entry of the package.
+    #[serde(flatten)]
+    pub package: InstalledPackage,
+    /// Whether the package is held.
+    pub held: bool,
+    /// The version requirement the package is pinned to.
+    pub pin: Option<String>,
+    /// The files of the installed version, relative to its store path.
+    pub files: Vec<PathBuf>,
+}
+
+/// run() runs the info subcommand which shows everything bote knows about a package before it
+/// is installed. If script is set, the build script is printed for review as well.
+pub async fn run(
+    node: &mut VeilidNode,
+    package: String,
+    library: Option<String>,
+    script: bool,
+) -> Result<(), anyhow::Error> {
+    let (address_library, package) = parse_package_address(&package);
+    let package = package.to_string();
+
+    let library = library::find_library(library.as_deref().or(address_library), &package)?;
+    let registry = Registry::load()?;
+    let cache = sync::read_cache(&library)?.ok_or_else(|| Error::NotFound {
+        whats_missing: format!("synced index of library {}", library),
+    })?;
+    let indexed = cache
+        .index
+        .packages
+        .get(&package)
+        .ok_or_else(|| Error::PackageNotFound {
+            package: package.clone(),
+            location: format!("the index of library {}", library),
+        })?;
+    let cached = cache.scripts.get(&package);
+    let (version, published) = match cached.and_then(|cached| {
+        indexed
+            .versions
+            .get_key_value(&cached.version)
+            .map(|(version, published)| (version.as_str(), published))
+    }) {
+        Some(current) => current,
+        None => indexed.latest().ok_or_else(|| Error::NotFound {
+            whats_missing: format!("published versions of {}", package),
+        })?,
+    };
+    let buildscript = match cached {
+        Some(_) => Some(library::fetch_build_script(&library, &package)?),
+        None => None,
+    };
+    let signature = match &buildscript {
+        Some(buildscript) => {
+            let crypto = node.crypto().await?;
+            match library::verify_build_script(&crypto, &library, &package, buildscript) {
+                Ok(()) => signature_status(&cache.header, published),
+                Err(e) => match e.downcast_ref::<Error>() {
+                    Some(Error::SignatureInvalid { reason, .. }) => SignatureStatus::Invalid {
+                        reason: reason.clone(),
+                    },
+                    _ => return Err(e),
+                },
+            }
+        }
+        None => SignatureStatus::Unknown,
+    };
+    if script && buildscript.is_none() {
+        return Err(Error::NotFound {
+            whats_missing: format!("cached build script of {}", package),
+        }
+        .into());
+    }
+
+    let mut versions: Vec<_> = indexed.versions.iter().collect();
+    versions.sort_by_key(|(version, _)| std::cmp::Reverse(Version::parse(version)));
+
+    let database = InstalledDatabase::load()?;
+    let installed = match database.get(&package) {
+        Some(installed) if installed.library.as_ref() == Some(&library) => Some(InstalledInfo {
+            package: installed.clone(),
+            held: database.is_held(&package),
+            pin: database.get_pin(&package).map(|pin| pin.to_string()),
+            files: store::read_manifest(&package, &installed.version)?.files,
+        }),
+        _ => None,
+    };
+    let installed_elsewhere = installed.is_none() && database.get(&package).is_some();
+
+    let info = PackageInfo {
+        library_alias: registry
+            .get_by_key(&library)
+            .map(|imported| imported.alias.clone()),
+        library,
+        version: version.to_string(),
+        metadata: published.metadata.clone(),
+        signature,
+        versions: versions
+            .into_iter()
+            .map(|(version, published)| VersionInfo {
+                version: version.clone(),
+                published_at: published.published_at,
+                prebuilt: published
+                    .artifacts
+                    .iter()
+                    .map(|artifact| artifact.target.clone())
+                    .collect(),
+            })
+            .collect(),
+        installed,
+        script: buildscript.filter(|_| script),
+        package,
+    };
+    output::set_result(&info)?;
+    print_info(&registry, &info, installed_elsewhere);
+
+    Ok(())
+}
+
+/// print_info() prints the details of a package for humans. installed_elsewhere is set if a
+/// package with the same name is installed from another source.
+fn print_info(registry: &Registry, info: &PackageInfo, installed_elsewhere: bool) {
+    let metadata = &info.metadata;
+
+    human!("Package:      {}", info.package);
+    match &info.library_alias {
+        Some(alias) => human!("Library:      {} ({})", alias, info.library),
+        None => human!("Library:      {}", info.library),
+    }
+    human!("Version:      {}", info.version);
+    if !metadata.description.is_empty() {
+        human!("Description:  {}", metadata.description);
+    }
+    if !metadata.keywords.is_empty() {
+        human!("Keywords:     {}", metadata.keywords.join(", "));
+    }
+    if let Some(license) = &metadata.license {
+        human!("License:      {}", license);
+    }
+
+    match &info.signature {
+        SignatureStatus::Valid { signer, owner } => human!(
+            "Signature:    valid, signed by the {} {}",
+            if *owner { "owner" } else { "writer" },
+            signer
+        ),
+        SignatureStatus::Invalid { reason } => human!("Signature:    invalid, {}", reason),
+        SignatureStatus::Unknown => {
+            human!("Signature:    unknown, the build script isn't synced yet")
+        }
+    }
+
+    print_references(registry, "Bote dependencies", &metadata.bote_dependencies);
+    print_references(registry, "Conflicts", &metadata.conflicts);
+    print_list(
+        "Required programs",
+        metadata.installed_program_dependencies.iter(),
+    );
+
+    human!("Versions:");
+    for version in &info.versions {
+        let published_at = UNIX_EPOCH + Duration::from_secs(version.published_at);
+
+        human!(
+            "  {}\tpublished {}{}",
+            version.version,
+            humantime::format_rfc3339_seconds(published_at),
+            if version.prebuilt.is_empty() {
+                String::new()
+            } else {
+                format!("\tprebuilt for {}", version.prebuilt.join(", "))
+            }
+        );
+    }
+
+    match &info.installed {
+        Some(installed) => {
+            let mut details = vec![if installed.package.explicit {
+                "explicitly installed"
+            } else {
+                "installed as a dependency"
+            }
+            .to_string()];
+            if installed.held {
+                details.push("held".to_string());
+            }
+            if let Some(pin) = &installed.pin {
+                details.push(format!("pinned to {}", pin));
+            }
+
+            human!(
+                "Installed:    {} ({})",
+                installed.package.version,
+                details.join(", ")
+            );
+            print_list("Files", installed.files.iter().map(|file| file.display()));
+        }
+        None if installed_elsewhere => {
+            human!("Installed:    no (a package with this name is installed from somewhere else)")
+        }
+        None => human!("Installed:    no"),
+    }
+
+    if let Some(script) = &info.script {
+        human!();
+        human!("{}", script);
+    }
+}
+
+/// signature_status() returns the status of a published version whose build script was
+/// verified.
+fn signature_status(header: &LibraryHeader, published: &PublishedVersion) -> SignatureStatus {
+    match &published.signature {
+        Some(signature) => SignatureStatus::Valid {
+            signer: signature.signer.clone(),
+            owner: header.owner == signature.signer,
+        },
+        None => SignatureStatus::Invalid {
+            reason: "not signed".to_string(),
+        },
+    }
+}
+
+/// print_references() prints package references with the aliases of their libraries.
+fn print_references(registry: &Registry, title: &str, references: &[PackageReference]) {
+    print_list(
+        title,
+        references.iter().map(|reference| {
+            let library = registry
+                .get_by_key(&reference.library)
+                .map(|imported| imported.alias.as_str())
+                .unwrap_or(&reference.library);
+
+            match &reference.requirement {
+                Some(requirement) => format!("{}/{} {}", library, reference.name, requirement),
+                None => format!("{}/{}", library, reference.name),
+            }
+        }),
+    );
+}
+
+/// print_list() prints a titled list with one entry per line, or "none" if it is empty.
+fn print_list<T: std::fmt::Display>(title: &str, entries: impl Iterator<Item = T>) {
+    let entries: Vec<String> = entries.map(|entry| entry.to_string()).collect();
+
+    if entries.is_empty() {
+        human!("{}: none", title);
+        return;
+    }
+
+    human!("{}:", title);
+    for entry in entries {
+        human!("  {}", entry);
+    }
+}
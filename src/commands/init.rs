@@ -1,4 +1,134 @@
-/// run() runs the init subcommand which should be run when installing bote.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: implement the init subcommand
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::Serialize;
+
+use crate::config;
+use crate::error::{with_path, Error};
+use crate::settings::Settings;
+use crate::{human, output};
+
+/// The result of the init subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct InitOutput {
+    /// The directories and files that were created.
+    pub created: Vec<PathBuf>,
+    /// The directory that has to be added to the PATH.
+    pub path: PathBuf,
+}
+
+/// directories() returns all directories bote needs.
+fn directories() -> Result<Vec<PathBuf>, anyhow::Error> {
+    Ok(vec![
+        config::get_app_directory()?,
+        config::get_config_directory()?,
+        config::get_state_directory()?,
+        config::get_cache_directory()?,
+        config::get_library_cache_directory()?,
+        config::get_store_directory()?,
+        config::get_profiles_directory()?,
+        config::get_database_directory()?,
+        config::get_veilid_table_store_path()?,
+        config::get_veilid_block_store_path()?,
+        config::get_veilid_protected_store_path()?,
+    ])
+}
+
+/// missing_directories() returns the directories bote needs that don't exist yet. It has to be
+/// called before the logger is set up, since the logger creates the state directory for its log
+/// file.
+pub fn missing_directories() -> Result<Vec<PathBuf>, anyhow::Error> {
+    Ok(directories()?
+        .into_iter()
+        .filter(|directory| !directory.exists())
+        .collect())
+}
+
+/// run() runs the init subcommand which should be run when installing bote. It creates all
+/// directories and files bote needs and can be run again safely, since it never overwrites
+/// anything that already exists. `missing` contains the directories that were missing before
+/// bote started, so directories created on startup are reported as well.
+pub fn run(missing: Vec<PathBuf>) -> Result<(), anyhow::Error> {
+    let mut created = Vec::new();
+
+    for directory in directories()? {
+        if !directory.exists() {
+            fs::create_dir_all(&directory).map_err(|e| with_path(e, &directory))?;
+        }
+        if missing.contains(&directory) {
+            created.push(directory);
+        }
+    }
+
+    let config_file = config::get_config_file_path()?;
+    if !config_file.exists() {
+        fs::write(
+            &config_file,
+            serde_json::to_string_pretty(&Settings::default())?,
+        )?;
+        created.push(config_file);
+    }
+
+    let certfile = config::get_veilid_certfile_path()?;
+    let keyfile = config::get_veilid_keyfile_path()?;
+    match (certfile.exists(), keyfile.exists()) {
+        (false, false) => {
+            generate_tls_certificate(&certfile, &keyfile)?;
+            created.push(certfile);
+            created.push(keyfile);
+        }
+        (true, false) => return Err(incomplete_certificate(&keyfile, &certfile).into()),
+        (false, true) => return Err(incomplete_certificate(&certfile, &keyfile).into()),
+        (true, true) => {}
+    }
+
+    let path = config::get_profile_path()?.join("bin");
+
+    if created.is_empty() {
+        human!("bote is already initialized, nothing to do.");
+    } else {
+        human!("Created:");
+        for path in &created {
+            human!("  {}", path.display());
+        }
+    }
+
+    human!();
+    human!("Add the following line to your shell configuration to use installed packages:");
+    human!("  export PATH=\"{}:$PATH\"", path.display());
+
+    output::set_result(&InitOutput { created, path })?;
+
+    Ok(())
+}
+
+/// incomplete_certificate() returns the error for a TLS certificate or private key whose
+/// counterpart is missing. A new pair isn't generated, since that would replace the existing file.
+fn incomplete_certificate(missing: &Path, existing: &Path) -> Error {
+    Error::IncompleteCertificate {
+        missing: missing.display().to_string(),
+        existing: existing.display().to_string(),
+    }
+}
+
+/// generate_tls_certificate() generates the self signed certificate and private key that veilid
+/// uses for TLS connections. The private key is created only readable by the current user.
+fn generate_tls_certificate(certfile: &Path, keyfile: &Path) -> Result<(), anyhow::Error> {
+    info!("Generating TLS certificate for veilid");
+
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+
+    fs::write(certfile, certificate.serialize_pem()?)?;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(keyfile)
+        .map_err(|e| with_path(e, keyfile))?
+        .write_all(certificate.serialize_private_key_pem().as_bytes())?;
+
+    Ok(())
 }
@@ -1,73 +1,776 @@
 mod buildscript;
+mod diagnostic;
 
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use anyhow::bail;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use regex::Regex;
 use rhai::{Engine, Scope, AST};
-use tempfile::tempdir;
+use tempfile::{tempdir, TempDir};
 use which::which_re;
 
-use crate::{config, error::Error};
+use crate::artifact;
+use crate::blob::BlobClient;
+use crate::commands::install::diagnostic::ScriptSource;
+use crate::database::{InstalledDatabase, InstalledPackage};
+use crate::dependencies::{
+    check_conflicts, check_dependents, check_pins, check_requirement, sort_by_dependencies,
+};
+use crate::error::{Error, ErrorKind};
+use crate::library::record::BinaryArtifact;
+use crate::node::VeilidNode;
+use crate::package::{parse_package_references, PackageReference};
+use crate::prompt::confirm;
+use crate::registry::{parse_package_address, Registry};
+use crate::version::Version;
+use crate::{config, human, library, output, profile, store};
 
-/// run() runs the install subcommand which is used to install a package.
-pub fn run() -> Result<(), anyhow::Error> {
-    // TODO: implement the install subcommand
+/// The metadata a build script returns about its package.
+#[derive(Debug, Clone)]
+pub struct BuildScriptMetadata {
+    pub version: String,
+    pub conflicts: Vec<PackageReference>,
+    pub bote_dependencies: Vec<PackageReference>,
+    pub installed_program_dependencies: Vec<String>,
+    pub description: String,
+    pub keywords: Vec<String>,
+    pub license: Option<String>,
+}
+
+/// run() runs the install subcommand which is used to install a package. Build scripts from
+/// libraries are verified before they are compiled, unless insecure is set. Prebuilt artifacts
+/// are used if the package version has one for this machine, unless build_from_source is set.
+/// Missing bote dependencies are installed from their libraries before the package. Build scripts
+/// from libraries the user doesn't trust need confirmation, unless insecure is set.
+pub async fn run(
+    node: &mut VeilidNode,
+    package: String,
+    library: Option<String>,
+    script: Option<PathBuf>,
+    insecure: bool,
+    build_from_source: bool,
+) -> Result<(), anyhow::Error> {
+    let (address_library, package) = parse_package_address(&package);
+    let package = package.to_string();
+
+    let (library, script_name, buildscript) = match script {
+        Some(script) => (
+            None,
+            script.display().to_string(),
+            fs::read_to_string(&script)?,
+        ),
+        None => {
+            let library = library::find_library(library.as_deref().or(address_library), &package)?;
+            let buildscript = library::fetch_build_script(&library, &package)?;
+            check_trust(&library, &package, insecure)?;
+            verify_build_script(node, &library, &package, &buildscript, insecure).await?;
+            (
+                Some(library),
+                library::build_script_name(&package),
+                buildscript,
+            )
+        }
+    };
+
+    let mut database = InstalledDatabase::load()?;
+
+    let metadata = read_metadata(&script_name, &buildscript)?;
+    let version = Version::parse(&metadata.version);
+    check_conflicts(&package, &metadata.conflicts, &database)?;
+    check_dependents(
+        &package,
+        &version,
+        database
+            .packages()
+            .filter(|installed| installed.name != package),
+    )?;
+
+    let mut versions = database.versions();
+    versions.insert(package.clone(), version.clone());
+    let missing = resolve_dependencies(
+        node,
+        &database,
+        &versions,
+        &[(package.as_str(), metadata.bote_dependencies.as_slice())],
+        insecure,
+    )
+    .await?;
+
+    check_pins(&package, &version, &metadata.bote_dependencies, &database)?;
+    for dependency in missing.values() {
+        check_pins(
+            &dependency.name,
+            &dependency.version,
+            &dependency.bote_dependencies,
+            &database,
+        )?;
+    }
+
+    let mut dependencies: BTreeMap<String, Vec<String>> = missing
+        .values()
+        .map(|dependency| {
+            (
+                dependency.name.clone(),
+                reference_names(&dependency.bote_dependencies),
+            )
+        })
+        .collect();
+    dependencies.insert(
+        package.clone(),
+        reference_names(&metadata.bote_dependencies),
+    );
+    let order = sort_by_dependencies(&dependencies)?;
+
+    if !missing.is_empty() {
+        human!(
+            "Installing the missing dependencies of {}: {}",
+            package,
+            missing.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    // every missing dependency is a dependency of the package, so the package comes last
+    let installed = async {
+        install_dependencies(node, &mut database, missing, order, build_from_source).await?;
+
+        match library {
+            Some(library) => {
+                install_from_library(
+                    node,
+                    &mut database,
+                    &package,
+                    &library,
+                    &buildscript,
+                    true,
+                    build_from_source,
+                )
+                .await
+            }
+            None => {
+                let blobs = blob_client(node, &buildscript).await?;
+                install_package(
+                    &mut database,
+                    &package,
+                    None,
+                    &script_name,
+                    &buildscript,
+                    true,
+                    blobs,
+                )
+                .await
+            }
+        }
+    }
+    .await;
+
+    // commit the finished installations even if one of them failed, so installed dependencies
+    // don't have to be built again
+    profile::commit(&database)?;
+
+    installed?;
+    output::set_result(&database.get(&package))?;
+
+    Ok(())
+}
+
+/// A missing bote dependency that is installed together with the packages that depend on it.
+pub struct PlannedDependency {
+    /// The name of the dependency.
+    pub name: String,
+    /// The DHT key of the library the dependency is installed from.
+    pub library: String,
+    /// The version of the cached build script.
+    pub version: Version,
+    /// The cached build script.
+    pub buildscript: String,
+    /// The bote dependencies of the dependency.
+    pub bote_dependencies: Vec<PackageReference>,
+}
+
+/// resolve_dependencies() finds the bote dependencies of packages that aren't installed yet,
+/// including the missing dependencies of those dependencies. `versions` contains the version
+/// every package has once the current operation is finished and `dependents` the packages that
+/// are installed or upgraded together with their bote dependencies. Missing dependencies are
+/// taken from the synced cache of their library and their build scripts are verified, unless
+/// insecure is set. Installed and missing dependencies have to satisfy the version requirements
+/// of the packages that depend on them.
+pub async fn resolve_dependencies(
+    node: &mut VeilidNode,
+    database: &InstalledDatabase,
+    versions: &BTreeMap<String, Version>,
+    dependents: &[(&str, &[PackageReference])],
+    insecure: bool,
+) -> Result<BTreeMap<String, PlannedDependency>, anyhow::Error> {
+    let mut missing = BTreeMap::new();
+    let mut versions = versions.clone();
+
+    let mut pending: Vec<(String, PackageReference)> = dependents
+        .iter()
+        .flat_map(|(dependent, dependencies)| {
+            dependencies
+                .iter()
+                .map(|dependency| (dependent.to_string(), dependency.clone()))
+        })
+        .collect();
+
+    while let Some((dependent, dependency)) = pending.pop() {
+        if let Some(version) = versions.get(&dependency.name) {
+            check_requirement(&dependent, &dependency, version)?;
+            continue;
+        }
+
+        let buildscript = library::fetch_build_script(&dependency.library, &dependency.name)?;
+        check_trust(&dependency.library, &dependency.name, insecure)?;
+        verify_build_script(
+            node,
+            &dependency.library,
+            &dependency.name,
+            &buildscript,
+            insecure,
+        )
+        .await?;
+        let metadata = read_metadata(&library::build_script_name(&dependency.name), &buildscript)?;
+        let version = Version::parse(&metadata.version);
+
+        check_requirement(&dependent, &dependency, &version)?;
+        check_conflicts(&dependency.name, &metadata.conflicts, database)?;
+
+        pending.extend(
+            metadata
+                .bote_dependencies
+                .iter()
+                .map(|reference| (dependency.name.clone(), reference.clone())),
+        );
+        versions.insert(dependency.name.clone(), version.clone());
+        missing.insert(
+            dependency.name.clone(),
+            PlannedDependency {
+                name: dependency.name,
+                library: dependency.library,
+                version,
+                buildscript,
+                bote_dependencies: metadata.bote_dependencies,
+            },
+        );
+    }
+
+    Ok(missing)
+}
+
+/// reference_names() returns the names of the referenced packages.
+pub fn reference_names(references: &[PackageReference]) -> Vec<String> {
+    references
+        .iter()
+        .map(|reference| reference.name.clone())
+        .collect()
+}
+
+/// install_dependencies() installs missing dependencies in the given order. Names in the order
+/// that aren't missing dependencies, like the package that depends on them, are skipped.
+/// Dependencies are installed from their library and aren't marked as explicitly installed.
+pub async fn install_dependencies(
+    node: &mut VeilidNode,
+    database: &mut InstalledDatabase,
+    mut missing: BTreeMap<String, PlannedDependency>,
+    order: Vec<String>,
+    build_from_source: bool,
+) -> Result<(), anyhow::Error> {
+    for name in order {
+        let Some(dependency) = missing.remove(&name) else {
+            continue;
+        };
+
+        info!("Installing dependency {}...", dependency.name);
+        install_from_library(
+            node,
+            database,
+            &dependency.name,
+            &dependency.library,
+            &dependency.buildscript,
+            false,
+            build_from_source,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// check_trust() makes sure that the user agrees to run the build script of a package from a
+/// library whose maintainers they don't trust. The user is asked for confirmation, unless
+/// insecure is set.
+pub fn check_trust(library: &str, package: &str, insecure: bool) -> Result<(), anyhow::Error> {
+    let registry = Registry::load()?;
+    if registry.is_trusted(library) {
+        return Ok(());
+    }
+
+    let alias = registry
+        .get_by_key(library)
+        .map(|imported| imported.alias.clone())
+        .unwrap_or_else(|| library.to_string());
+
+    if insecure {
+        warn!(
+            "Running the build script of {} from the untrusted library {} because of --insecure",
+            package, alias
+        );
+        return Ok(());
+    }
+
+    let question = format!(
+        "{} comes from the library {}, whose maintainers you don't trust. Run its build script?",
+        package, alias
+    );
+    if !confirm(&question)? {
+        return Err(Error::UntrustedLibrary { library: alias }.into());
+    }
+
+    Ok(())
+}
+
+/// verify_build_script() verifies the signature of a build script from a library. If insecure is
+/// set, the verification is skipped with a warning.
+pub async fn verify_build_script(
+    node: &mut VeilidNode,
+    library: &str,
+    package: &str,
+    buildscript: &str,
+    insecure: bool,
+) -> Result<(), anyhow::Error> {
+    if insecure {
+        warn!(
+            "Skipping the signature verification of {} because of --insecure",
+            package
+        );
+        return Ok(());
+    }
+
+    library::verify_build_script(&node.crypto().await?, library, package, buildscript)
+}
+
+/// setup_engine() creates a rhai engine that can run build scripts.
+fn setup_engine() -> Engine {
     let mut engine = Engine::new();
     buildscript::setup_rhai_engine(&mut engine);
+    engine
+}
+
+/// read_metadata() compiles a build script and returns the metadata of its package without
+/// installing it. The file name of the script is shown in errors.
+pub fn read_metadata(
+    script_name: &str,
+    buildscript: &str,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let script = ScriptSource::new(script_name, buildscript);
+    let engine = setup_engine();
+    let ast = engine
+        .compile(buildscript)
+        .map_err(|e| script.compile_error(e))?;
+    let mut scope = Scope::new();
+
+    get_metadata(&engine, &ast, &mut scope, script)
+}
+
+/// REQUIRED_FUNCTIONS are the functions every build script has to define.
+const REQUIRED_FUNCTIONS: [&str; 7] = [
+    "version",
+    "conflicts",
+    "bote_dependencies",
+    "installed_program_dependencies",
+    "prepare",
+    "download",
+    "install",
+];
+
+/// validate_build_script() checks that a build script compiles and defines all required
+/// functions without parameters. The metadata of its package is returned.
+pub fn validate_build_script(
+    script_name: &str,
+    buildscript: &str,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let script = ScriptSource::new(script_name, buildscript);
+    let engine = setup_engine();
+    let ast = engine
+        .compile(buildscript)
+        .map_err(|e| script.compile_error(e))?;
 
-    let mut buildfile = File::open("./build.bote.rhai")?;
-    let mut buildscript = String::new();
-    buildfile.read_to_string(&mut buildscript)?;
+    for required in REQUIRED_FUNCTIONS {
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == required && function.params.is_empty())
+        {
+            return Err(Error::InvalidBuildScript {
+                reason: format!("missing function {}()", required),
+            }
+            .into());
+        }
+    }
+
+    let mut scope = Scope::new();
+    get_metadata(&engine, &ast, &mut scope, script)
+}
+
+/// blob_client() returns a blob client if a build script downloads blobs from veilid. The node is
+/// only attached to the network in that case.
+pub async fn blob_client(
+    node: &mut VeilidNode,
+    buildscript: &str,
+) -> Result<Option<BlobClient>, anyhow::Error> {
+    if !buildscript::uses_veilid(buildscript) {
+        return Ok(None);
+    }
+
+    Ok(Some(BlobClient::new(node).await?))
+}
+
+/// install_from_library() installs the cached version of a package from a library. If the version
+/// has a prebuilt artifact for the target of this machine, the artifact is installed instead of
+/// building the package, unless build_from_source is set. The package is built from source if
+/// there is no artifact or it can't be downloaded.
+pub async fn install_from_library(
+    node: &mut VeilidNode,
+    database: &mut InstalledDatabase,
+    name: &str,
+    library: &str,
+    buildscript: &str,
+    explicit: bool,
+    build_from_source: bool,
+) -> Result<(), anyhow::Error> {
+    let prebuilt = if build_from_source {
+        None
+    } else {
+        library::find_artifact(library, name, artifact::TARGET)?
+    };
+
+    if let Some(prebuilt) = prebuilt {
+        info!(
+            "Installing the prebuilt artifact of {} for {}",
+            name,
+            artifact::TARGET
+        );
+        let result = async {
+            let blobs = BlobClient::new(node).await?;
+            install_prebuilt(
+                database,
+                name,
+                library,
+                buildscript,
+                &prebuilt,
+                &blobs,
+                explicit,
+            )
+            .await
+        }
+        .await;
+
+        // integrity and safety errors like a checksum mismatch are returned, since building
+        // the package instead would hide a tampered artifact
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if ErrorKind::of(&e) == ErrorKind::NetworkUnavailable => warn!(
+                "Failed to download the prebuilt artifact of {}, building it from source: {}",
+                name, e
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let blobs = blob_client(node, buildscript).await?;
+    install_package(
+        database,
+        name,
+        Some(library.to_string()),
+        &library::build_script_name(name),
+        buildscript,
+        explicit,
+        blobs,
+    )
+    .await
+}
+
+/// install_prebuilt() downloads a prebuilt artifact of a package into a staging directory, moves
+/// the staged tree into the store and records it in the installed package database. The artifact
+/// has to match the hash in the index of the library.
+async fn install_prebuilt(
+    database: &mut InstalledDatabase,
+    name: &str,
+    library: &str,
+    buildscript: &str,
+    prebuilt: &BinaryArtifact,
+    blobs: &BlobClient,
+    explicit: bool,
+) -> Result<(), anyhow::Error> {
+    let metadata = read_metadata(&library::build_script_name(name), buildscript)?;
 
-    let ast = engine.compile(buildscript)?;
+    let staging = store::create_staging_directory()?;
+    artifact::download(blobs, prebuilt, staging.path()).await?;
+
+    add_to_store(
+        database,
+        name,
+        Some(library.to_string()),
+        metadata,
+        staging,
+        explicit,
+    )
+}
+
+/// install_package() installs a package from its build script into a staging directory, moves
+/// the staged tree into the store and records it in the installed package database. The caller
+/// has to commit the database to make the package part of a new generation. Build scripts that
+/// download blobs from veilid need a blob client.
+pub async fn install_package(
+    database: &mut InstalledDatabase,
+    name: &str,
+    library: Option<String>,
+    script_name: &str,
+    buildscript: &str,
+    explicit: bool,
+    blobs: Option<BlobClient>,
+) -> Result<(), anyhow::Error> {
+    let staging = store::create_staging_directory()?;
+    let metadata = build_package(script_name, buildscript, staging.path(), blobs).await?;
+
+    add_to_store(database, name, library, metadata, staging, explicit)
+}
+
+/// build_package() runs a build script in a temporary working directory, so it installs its
+/// package into the given prefix. The metadata of the package is returned. The file name of the
+/// script is shown in errors. The script runs on a blocking thread, so it doesn't keep the
+/// runtime from handling an interrupt.
+pub async fn build_package(
+    script_name: &str,
+    buildscript: &str,
+    prefix: &Path,
+    blobs: Option<BlobClient>,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let script_name = script_name.to_string();
+    let buildscript = buildscript.to_string();
+    let prefix = prefix.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        run_build_script(&script_name, &buildscript, &prefix, blobs)
+    })
+    .await?
+}
+
+/// run_build_script() compiles and executes a build script in a temporary working directory.
+fn run_build_script(
+    script_name: &str,
+    buildscript: &str,
+    prefix: &Path,
+    blobs: Option<BlobClient>,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let mut engine = setup_engine();
+    buildscript::register_install_prefix(&mut engine, prefix);
+    if let Some(blobs) = blobs {
+        buildscript::register_download_veilid(&mut engine, blobs);
+    }
+    let script = ScriptSource::new(script_name, buildscript);
+    let ast = engine
+        .compile(buildscript)
+        .map_err(|e| script.compile_error(e))?;
     let mut scope = Scope::new();
 
     // set the current working directory to a secure temporary directory
-    let working_directory = tempdir()?;
-    std::env::set_current_dir(working_directory.path())?;
-    debug!(
-        "Changed working directory to {}",
-        working_directory.path().display()
-    );
+    let _working_directory = WorkingDirectory::enter()?;
+
+    execute_build_script(&engine, &ast, &mut scope, script)
+}
 
-    execute_build_script(&engine, &ast, &mut scope)?;
+/// WorkingDirectory is a temporary working directory for a build script. The previous working
+/// directory is restored when it is dropped, also if the script failed, and the temporary
+/// directory is deleted afterwards.
+struct WorkingDirectory {
+    previous: PathBuf,
+    directory: TempDir,
+}
 
-    // reset working directory
-    std::env::set_current_dir(config::get_app_directory()?)?;
+impl WorkingDirectory {
+    /// enter() creates a temporary directory and changes the working directory to it. If the
+    /// current working directory doesn't exist anymore, the app directory is restored instead.
+    fn enter() -> Result<Self, anyhow::Error> {
+        let previous = match std::env::current_dir() {
+            Ok(previous) => previous,
+            Err(_) => config::get_app_directory()?,
+        };
+        let directory = tempdir()?;
+        std::env::set_current_dir(directory.path())?;
+        debug!(
+            "Changed working directory to {}",
+            directory.path().display()
+        );
 
-    working_directory.close()?;
+        Ok(WorkingDirectory {
+            previous,
+            directory,
+        })
+    }
+}
+
+impl Drop for WorkingDirectory {
+    fn drop(&mut self) {
+        if let Err(e) = std::env::set_current_dir(&self.previous) {
+            warn!(
+                "Failed to restore the working directory {}: {}",
+                self.previous.display(),
+                e
+            );
+        }
+        debug!(
+            "Restored working directory {}, removing {}",
+            self.previous.display(),
+            self.directory.path().display()
+        );
+    }
+}
+
+/// add_to_store() moves a staged tree into the store and records the package in the installed
+/// package database.
+fn add_to_store(
+    database: &mut InstalledDatabase,
+    name: &str,
+    library: Option<String>,
+    metadata: BuildScriptMetadata,
+    staging: TempDir,
+    explicit: bool,
+) -> Result<(), anyhow::Error> {
+    let package = InstalledPackage::new(
+        name,
+        &metadata.version,
+        library,
+        explicit,
+        metadata.bote_dependencies,
+    );
+    store::add(staging, package.clone())?;
+    database.insert(package);
+    info!("Installed {} {}", name, metadata.version);
 
     Ok(())
 }
 
-fn execute_build_script(
+fn get_metadata(
     engine: &Engine,
     ast: &AST,
     scope: &mut Scope,
-) -> Result<(), anyhow::Error> {
-    let version = engine.call_fn::<String>(scope, ast, "version", ())?;
-    info!("Package version: {}", version);
-
-    info!("Checking conflicts..."); // TODO: Implement conflict checks
-    let conflicts = engine
-        .call_fn::<rhai::Array>(scope, ast, "conflicts", ())?
-        .to_vec();
-    info!("Conflicts: {:?}", conflicts);
-
-    info!("Checking dependencies..."); // TODO: Implement bote dependency checks
-    let bote_dependencies = engine
-        .call_fn::<rhai::Array>(scope, ast, "bote_dependencies", ())?
-        .to_vec();
-    info!("Bote dependencies: {:?}", bote_dependencies);
+    script: ScriptSource,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let version = engine
+        .call_fn::<String>(scope, ast, "version", ())
+        .map_err(|e| script.call_error("version", e))?;
+    let conflicts = parse_package_references(
+        engine
+            .call_fn::<rhai::Array>(scope, ast, "conflicts", ())
+            .map_err(|e| script.call_error("conflicts", e))?,
+    )?;
+    let bote_dependencies = parse_package_references(
+        engine
+            .call_fn::<rhai::Array>(scope, ast, "bote_dependencies", ())
+            .map_err(|e| script.call_error("bote_dependencies", e))?,
+    )?;
+    let (conflicts, bote_dependencies) = {
+        let registry = Registry::load()?;
+        (
+            canonicalize_references(&registry, conflicts),
+            canonicalize_references(&registry, bote_dependencies),
+        )
+    };
     let installed_program_dependencies = engine
-        .call_fn::<rhai::Array>(scope, ast, "installed_program_dependencies", ())?
-        .to_vec();
+        .call_fn::<rhai::Array>(scope, ast, "installed_program_dependencies", ())
+        .map_err(|e| script.call_error("installed_program_dependencies", e))?
+        .into_iter()
+        .map(|dependency| dependency.to_string())
+        .collect();
+
+    let description = call_optional(engine, scope, ast, script, "description")?
+        .map(|description| description.to_string())
+        .unwrap_or_default();
+    let keywords = match call_optional(engine, scope, ast, script, "keywords")? {
+        Some(keywords) => {
+            let keywords_string = keywords.to_string();
+            keywords
+                .try_cast::<rhai::Array>()
+                .ok_or(Error::Conversion {
+                    from: keywords_string,
+                    into: "array of keywords".to_string(),
+                })?
+                .into_iter()
+                .map(|keyword| keyword.to_string())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let license =
+        call_optional(engine, scope, ast, script, "license")?.map(|license| license.to_string());
+
+    Ok(BuildScriptMetadata {
+        version,
+        conflicts,
+        bote_dependencies,
+        installed_program_dependencies,
+        description,
+        keywords,
+        license,
+    })
+}
 
-    for dependency in installed_program_dependencies {
-        if which_re(Regex::new(dependency.to_string().as_str())?).is_err() {
+/// call_optional() calls a function a build script doesn't have to define, like description().
+/// None is returned if the build script doesn't define the function.
+fn call_optional(
+    engine: &Engine,
+    scope: &mut Scope,
+    ast: &AST,
+    script: ScriptSource,
+    name: &str,
+) -> Result<Option<rhai::Dynamic>, anyhow::Error> {
+    if !ast
+        .iter_functions()
+        .any(|function| function.name == name && function.params.is_empty())
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        engine
+            .call_fn::<rhai::Dynamic>(scope, ast, name, ())
+            .map_err(|e| script.call_error(name, e))?,
+    ))
+}
+
+/// canonicalize_references() replaces library aliases in package references with the DHT keys
+/// of the libraries, so installed packages never depend on local aliases.
+fn canonicalize_references(
+    registry: &Registry,
+    references: Vec<PackageReference>,
+) -> Vec<PackageReference> {
+    references
+        .into_iter()
+        .map(|reference| PackageReference {
+            library: registry.canonical_key(&reference.library),
+            ..reference
+        })
+        .collect()
+}
+
+fn execute_build_script(
+    engine: &Engine,
+    ast: &AST,
+    scope: &mut Scope,
+    script: ScriptSource,
+) -> Result<BuildScriptMetadata, anyhow::Error> {
+    let metadata = get_metadata(engine, ast, scope, script)?;
+    info!("Package version: {}", metadata.version);
+
+    info!("Conflicts: {:?}", metadata.conflicts);
+
+    info!("Bote dependencies: {:?}", metadata.bote_dependencies);
+
+    for dependency in &metadata.installed_program_dependencies {
+        if which_re(Regex::new(dependency)?).is_err() {
             error!(
                 "Failed to confirm existense of {}. Please check if you have {} installed.",
                 dependency, dependency
@@ -79,13 +782,19 @@ fn execute_build_script(
     }
 
     info!("Preparing installation...");
-    engine.call_fn(scope, ast, "prepare", ())?;
+    engine
+        .call_fn::<rhai::Dynamic>(scope, ast, "prepare", ())
+        .map_err(|e| script.call_error("prepare", e))?;
 
     info!("Downloading files...");
-    engine.call_fn(scope, ast, "download", ())?;
+    engine
+        .call_fn::<rhai::Dynamic>(scope, ast, "download", ())
+        .map_err(|e| script.call_error("download", e))?;
 
     info!("Building and installing program...");
-    engine.call_fn(scope, ast, "install", ())?;
+    engine
+        .call_fn::<rhai::Dynamic>(scope, ast, "install", ())
+        .map_err(|e| script.call_error("install", e))?;
 
-    Ok(())
+    Ok(metadata)
 }
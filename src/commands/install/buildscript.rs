@@ -14,6 +14,9 @@ use shlex::Shlex;
 use tar::Archive;
 use zip::ZipArchive;
 
+use crate::blob::BlobClient;
+use crate::error::Error;
+
 // HACK: The current code style for the functions is really ugly. I should refactor it by
 // implementing the From trait for the bote error type to Box<EvalAltResult> and by extracting
 // common functionality into their own functions. But for now, this works.
@@ -30,11 +33,50 @@ pub fn setup_rhai_engine(engine: &mut Engine) {
         .register_fn("extract_lzma", extract_lzma)
         .register_fn("extract_bzip2", extract_bzip2)
         .register_fn("extract_zip", extract_zip)
-        .register_fn("extract_tar_archive", extract_tar_archive);
+        .register_fn("extract_tar_archive", extract_tar_archive)
+        .register_fn("to_string", |error: &mut Error| error.to_string());
 
     url.register_into_engine(engine);
 }
 
+/// register_install_prefix() registers the install_prefix() function which returns the directory
+/// a build script has to install its package into.
+pub fn register_install_prefix(engine: &mut Engine, prefix: &Path) {
+    let prefix = prefix.to_string_lossy().to_string();
+    engine.register_fn("install_prefix", move || prefix.clone());
+}
+
+/// register_download_veilid() registers the download_veilid() function which downloads a blob
+/// from the veilid DHT to a path relative to the working directory.
+pub fn register_download_veilid(engine: &mut Engine, blobs: BlobClient) {
+    engine.register_fn(
+        "download_veilid",
+        move |blob: ImmutableString, path: ImmutableString| -> Result<(), Box<EvalAltResult>> {
+            info!("Downloading blob {} to {}", blob, path);
+
+            // build scripts run synchronously, so the download blocks the current thread
+            let result = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current()
+                    .block_on(blobs.download(blob.as_str(), Path::new(path.as_str())))
+            });
+
+            result.map_err(|e| {
+                error!("Failed to download blob {}: {}", blob, e);
+                match e.downcast::<Error>() {
+                    Ok(e) => e.into(),
+                    Err(e) => e.to_string().into(),
+                }
+            })
+        },
+    );
+}
+
+/// uses_veilid() returns whether a build script uses functions that need the veilid network, so
+/// bote only attaches to the network for build scripts that need it.
+pub fn uses_veilid(buildscript: &str) -> bool {
+    buildscript.contains("download_veilid")
+}
+
 /// clone_git_repo() clones a git repository to a path relative to the working directory.
 fn clone_git_repo(repo: ImmutableString, path: ImmutableString) -> Result<(), Box<EvalAltResult>> {
     info!("Cloning repository {}", repo);
@@ -85,10 +127,21 @@ fn execute_system_command(cmd: ImmutableString) -> Result<(), Box<EvalAltResult>
     }
     let mut child_command = child_command.unwrap();
 
-    if let Err(e) = child_command.wait() {
+    let status = child_command.wait();
+    if let Err(e) = status {
         error!("Command execution of \"{}\" failed: {}", cmd, e);
         return Err(e.to_string().into());
     }
+    let status = status.unwrap();
+
+    if !status.success() {
+        error!("Command \"{}\" failed with {}", cmd, status);
+        return Err(Error::CommandFailed {
+            command: cmd.to_string(),
+            code: status.code(),
+        }
+        .into());
+    }
 
     Ok(())
 }
@@ -0,0 +1,109 @@
+use rhai::{EvalAltResult, ParseError, Position};
+
+use crate::error::{Error, ScriptError, ScriptFrame};
+
+/// The name and the source of a build script, so its errors can point into the source.
+#[derive(Clone, Copy, Debug)]
+pub struct ScriptSource<'a> {
+    /// The file name of the build script.
+    pub name: &'a str,
+    /// The source code of the build script.
+    pub source: &'a str,
+}
+
+impl<'a> ScriptSource<'a> {
+    /// new() creates the source of a build script from its file name and source code.
+    pub fn new(name: &'a str, source: &'a str) -> Self {
+        Self { name, source }
+    }
+
+    /// compile_error() converts an error of compiling the build script into a bote error.
+    pub fn compile_error(&self, error: ParseError) -> Error {
+        self.error(
+            ScriptError::COMPILE_STAGE,
+            error.err_type().to_string(),
+            error.position(),
+            Vec::new(),
+            None,
+        )
+    }
+
+    /// call_error() converts an error the build script raised while bote called one of its
+    /// functions, like download(), into a bote error. Nested calls of script functions become
+    /// the call stack. If a native function raised a bote error, like a failed command, it is
+    /// kept as the cause, so the class of the error doesn't change.
+    pub fn call_error(&self, stage: &str, error: Box<EvalAltResult>) -> Error {
+        let mut call_stack = Vec::new();
+        let mut function = stage.to_string();
+        let mut error = *error;
+
+        // rhai wraps errors in nested calls once per call, with the position of the call
+        loop {
+            match error {
+                EvalAltResult::ErrorInFunctionCall(name, _, inner, position) => {
+                    call_stack.push(self.frame(function, position));
+                    function = name;
+                    error = *inner;
+                }
+                EvalAltResult::ErrorInModule(_, inner, _) => error = *inner,
+                _ => break,
+            }
+        }
+
+        let position = error.position();
+        if !call_stack.is_empty() {
+            call_stack.push(self.frame(function, position));
+        }
+
+        match error {
+            EvalAltResult::ErrorRuntime(value, _) if value.is::<Error>() => {
+                let cause = value.cast::<Error>();
+                self.error(
+                    stage,
+                    cause.to_string(),
+                    position,
+                    call_stack,
+                    Some(Box::new(cause)),
+                )
+            }
+            mut error => {
+                error.clear_position();
+                self.error(stage, error.to_string(), position, call_stack, None)
+            }
+        }
+    }
+
+    /// frame() creates a frame of the call stack for a function running at a position.
+    fn frame(&self, function: String, position: Position) -> ScriptFrame {
+        ScriptFrame {
+            function,
+            line: position.line(),
+            column: position.position(),
+        }
+    }
+
+    /// error() creates a script error at a position of the build script.
+    fn error(
+        &self,
+        stage: &str,
+        message: String,
+        position: Position,
+        call_stack: Vec<ScriptFrame>,
+        cause: Option<Box<Error>>,
+    ) -> Error {
+        let line = position.line();
+
+        Error::Script(Box::new(ScriptError {
+            script: self.name.to_string(),
+            stage: stage.to_string(),
+            message,
+            line,
+            column: position.position(),
+            source_line: line
+                .and_then(|line| self.source.lines().nth(line.checked_sub(1)?))
+                .map(|source_line| source_line.to_string()),
+            call_stack,
+            cause,
+        }))
+    }
+}
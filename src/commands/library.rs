@@ -1,4 +1,386 @@
+use std::str::FromStr;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Subcommand;
+use log::{info, warn};
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use veilid_core::{PublicKey, RoutingContext};
+
+use crate::database::InstalledDatabase;
+use crate::error::Error;
+use crate::library::sync::SyncSummary;
+use crate::library::{self, record};
+use crate::node::VeilidNode;
+use crate::registry::{ImportedLibrary, Registry};
+use crate::secrets;
+use crate::{human, output};
+
+#[derive(Subcommand)]
+pub enum LibraryCommands {
+    #[command(about = "Create a new library")]
+    Create {
+        #[arg(help = "The name of the library")]
+        name: String,
+        #[arg(long, default_value = "", help = "A short description of the library")]
+        description: String,
+        #[arg(
+            long = "writer",
+            value_name = "PUBLIC_KEY",
+            help = "The public key of a co-maintainer who can publish to the library"
+        )]
+        writers: Vec<String>,
+    },
+    #[command(about = "Show the public key co-maintainers need to add you as a writer")]
+    Identity,
+    #[command(about = "Import a library")]
+    Import {
+        #[arg(help = "The DHT key of the library")]
+        key: String,
+        #[arg(long, help = "The local name of the library (defaults to its name)")]
+        alias: Option<String>,
+        #[arg(long, help = "Trust the maintainers of the library")]
+        trust: bool,
+    },
+    #[command(about = "List all imported libraries")]
+    List,
+    #[command(about = "Pull the package indexes of imported libraries into the local cache")]
+    Sync {
+        #[arg(help = "The aliases of the libraries to sync (defaults to all)")]
+        aliases: Vec<String>,
+        #[arg(long, help = "Keep running and sync libraries when they change")]
+        watch: bool,
+        #[arg(
+            long,
+            default_value_t = 60,
+            help = "The seconds between two syncs in watch mode"
+        )]
+        interval: u64,
+    },
+    #[command(about = "Remove an imported library")]
+    Remove {
+        #[arg(help = "The alias of the library")]
+        alias: String,
+    },
+}
+
+/// The result of library identity in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct IdentityOutput {
+    /// The public key co-maintainers need to add this bote installation as a writer.
+    pub public_key: String,
+}
+
+/// The result of syncing a library in the JSON output of library sync.
+#[derive(Serialize, Debug)]
+pub struct SyncOutput {
+    /// The alias of the library.
+    pub alias: String,
+    /// The DHT key of the library.
+    pub key: String,
+    /// What changed during the sync.
+    #[serde(flatten)]
+    pub summary: SyncSummary,
+}
+
 /// run() runs the library subcommand which can be used to create, import or update a library.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: Create functionality to import or create libraries
+pub async fn run(command: LibraryCommands, node: &mut VeilidNode) -> Result<(), anyhow::Error> {
+    match command {
+        LibraryCommands::Create {
+            name,
+            description,
+            writers,
+        } => create(node, name, description, writers).await,
+        LibraryCommands::Identity => identity(node).await,
+        LibraryCommands::Import { key, alias, trust } => import(node, key, alias, trust).await,
+        LibraryCommands::List => list(),
+        LibraryCommands::Sync {
+            aliases,
+            watch,
+            interval,
+        } => sync(node, aliases, watch, interval).await,
+        LibraryCommands::Remove { alias } => remove(alias),
+    }
+}
+
+/// create() creates a library record on the DHT, keeps its owner keypair in the protected store
+/// and imports the library. The imported library is the result in the JSON output.
+async fn create(
+    node: &mut VeilidNode,
+    name: String,
+    description: String,
+    writers: Vec<String>,
+) -> Result<(), anyhow::Error> {
+    let writers = writers
+        .iter()
+        .map(|writer| {
+            PublicKey::from_str(writer).map_err(|_| Error::Conversion {
+                from: writer.clone(),
+                into: "public key".to_string(),
+            })
+        })
+        .collect::<Result<Vec<PublicKey>, Error>>()?;
+
+    // the creator imports the library right away so they can publish to it, which needs a free
+    // alias
+    let mut registry = Registry::load()?;
+    registry.check_alias(&name)?;
+
+    let routing_context = node.routing_context().await?;
+    let (library, owner) = record::create(&routing_context, &name, &description, &writers).await?;
+    info!("Created library record {}", library.key);
+
+    secrets::save_library_owner(node.api().await?, &library.key, &owner).await?;
+
+    let imported = ImportedLibrary {
+        alias: name.clone(),
+        key: library.key.clone(),
+        name: name.clone(),
+        description,
+        last_sync: None,
+        trusted: true,
+    };
+    output::set_result(&imported)?;
+    registry.insert(imported)?;
+    registry.save()?;
+
+    human!("Created library {}.", name);
+    if !writers.is_empty() {
+        human!("{} co-maintainers can publish to it.", writers.len());
+    }
+    human!();
+    human!("Share its key so others can import it:");
+    human!("  {}", library.key);
+
+    Ok(())
+}
+
+/// identity() prints the public key of the writer keypair of this bote installation. The node
+/// isn't attached to the network, so this works offline.
+async fn identity(node: &mut VeilidNode) -> Result<(), anyhow::Error> {
+    let identity = secrets::identity(node.start().await?).await?;
+    output::set_result(&IdentityOutput {
+        public_key: identity.key.to_string(),
+    })?;
+    human!("{}", identity.key);
+
+    Ok(())
+}
+
+/// import() reads the header of a library from the DHT and adds the library to the registry. The
+/// imported library is the result in the JSON output. Importing an imported library again with
+/// trusted set marks it as trusted.
+async fn import(
+    node: &mut VeilidNode,
+    key: String,
+    alias: Option<String>,
+    trusted: bool,
+) -> Result<(), anyhow::Error> {
+    let key = record::parse_key(&key)?.to_string();
+    let mut registry = Registry::load()?;
+
+    if let Some(existing) = registry.get_by_key(&key).cloned() {
+        if trusted && !existing.trusted {
+            let library = registry
+                .get_mut(&existing.alias)
+                .expect("the library is imported");
+            library.trusted = true;
+            output::set_result(library)?;
+            registry.save()?;
+            human!("Library {} is now trusted.", existing.alias);
+            return Ok(());
+        }
+
+        output::set_result(&existing)?;
+        human!("Library {} is already imported as {}.", key, existing.alias);
+        return Ok(());
+    }
+
+    let routing_context = node.routing_context().await?;
+    let library = record::read(&routing_context, &key).await?;
+
+    let alias = alias.unwrap_or_else(|| library.header.name.clone());
+    let imported = ImportedLibrary {
+        alias: alias.clone(),
+        key,
+        name: library.header.name,
+        description: library.header.description,
+        last_sync: None,
+        trusted,
+    };
+    output::set_result(&imported)?;
+    registry.insert(imported)?;
+    registry.save()?;
+
+    human!("Imported library {}.", alias);
+
+    Ok(())
+}
+
+/// list() prints all imported libraries.
+fn list() -> Result<(), anyhow::Error> {
+    let registry = Registry::load()?;
+    output::set_result(&registry.libraries().collect::<Vec<_>>())?;
+
+    if registry.libraries().next().is_none() {
+        human!("No libraries imported.");
+        return Ok(());
+    }
+
+    for library in registry.libraries() {
+        let last_sync = match library.last_sync {
+            Some(last_sync) => {
+                let last_sync = UNIX_EPOCH + Duration::from_secs(last_sync);
+                format!("synced {}", humantime::format_rfc3339_seconds(last_sync))
+            }
+            None => "never synced".to_string(),
+        };
+
+        human!(
+            "{}\t{}\t{}{}",
+            library.alias,
+            library.key,
+            last_sync,
+            if library.trusted { "\ttrusted" } else { "" }
+        );
+        if !library.description.is_empty() {
+            human!("\t{}", library.description);
+        }
+    }
+
+    Ok(())
+}
+
+/// sync() syncs imported libraries into the local cache. In watch mode it keeps running and
+/// syncs a library again when veilid reports a change of its record. The result in the JSON
+/// output contains the SyncOutput of every library of the first sync.
+async fn sync(
+    node: &mut VeilidNode,
+    aliases: Vec<String>,
+    watch: bool,
+    interval: u64,
+) -> Result<(), anyhow::Error> {
+    let registry = Registry::load()?;
+    let libraries: Vec<ImportedLibrary> = if aliases.is_empty() {
+        registry.libraries().cloned().collect()
+    } else {
+        aliases
+            .iter()
+            .map(|alias| registry.resolve(alias).cloned())
+            .collect::<Result<_, _>>()?
+    };
+
+    if libraries.is_empty() {
+        human!("No libraries imported.");
+        return Ok(());
+    }
+
+    let mut value_changes = node.subscribe_value_changes();
+    let routing_context = node.routing_context().await?;
+
+    let mut synced = Vec::new();
+    for library in &libraries {
+        synced.push(sync_library(&routing_context, library).await?);
+        output::set_result(&synced)?;
+    }
+
+    if !watch {
+        return Ok(());
+    }
+
+    // veilid 0.2.1 doesn't implement watch_dht_values yet, so bote can't ask for notifications.
+    // Value changes veilid reports anyway are used, and all libraries are polled as a fallback.
+    human!("Watching for changes, press Ctrl-C to stop.");
+    let interval = Duration::from_secs(interval);
+
+    loop {
+        let changed: Vec<&ImportedLibrary> = tokio::select! {
+            change = value_changes.recv() => match change {
+                Ok(change) => {
+                    let key = change.key.to_string();
+                    libraries.iter().filter(|library| library.key == key).collect()
+                }
+                Err(RecvError::Lagged(_)) => libraries.iter().collect(),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            _ = tokio::time::sleep(interval) => libraries.iter().collect(),
+        };
+
+        for library in changed {
+            if let Err(e) = sync_library(&routing_context, library).await {
+                warn!("Failed to sync {}: {}", library.alias, e);
+            }
+        }
+    }
+}
+
+/// sync_library() syncs a single library and records the time of the sync in the registry.
+async fn sync_library(
+    routing_context: &RoutingContext,
+    library: &ImportedLibrary,
+) -> Result<SyncOutput, anyhow::Error> {
+    let summary = library::sync::sync(routing_context, &library.key).await?;
+
+    let mut registry = Registry::load()?;
+    if let Some(imported) = registry.get_mut(&library.alias) {
+        imported.last_sync = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+        );
+
+        if let Some(cache) = library::sync::read_cache(&library.key)? {
+            imported.name = cache.header.name;
+            imported.description = cache.header.description;
+        }
+    }
+    registry.save()?;
+
+    if summary.changed {
+        human!(
+            "{}: {} build scripts fetched, {} removed",
+            library.alias,
+            summary.fetched_scripts,
+            summary.removed_scripts
+        );
+    } else {
+        human!("{}: up to date", library.alias);
+    }
+
+    Ok(SyncOutput {
+        alias: library.alias.clone(),
+        key: library.key.clone(),
+        summary,
+    })
+}
+
+/// remove() removes a library from the registry and deletes its cached build scripts.
+/// Installed packages of the library are kept, but can't be upgraded anymore. The removed library
+/// is the result in the JSON output.
+fn remove(alias: String) -> Result<(), anyhow::Error> {
+    let mut registry = Registry::load()?;
+    let library = registry.remove(&alias).ok_or(Error::NotFound {
+        whats_missing: format!("imported library {}", alias),
+    })?;
+
+    library::remove_cache(&library.key)?;
+    registry.save()?;
+    output::set_result(&library)?;
+
+    let installed = InstalledDatabase::load()?
+        .packages()
+        .filter(|package| package.library.as_deref() == Some(library.key.as_str()))
+        .count();
+    if installed > 0 {
+        warn!(
+            "{} installed packages come from {} and can't be upgraded until it is imported again",
+            installed, alias
+        );
+    }
+
+    human!("Removed library {}.", alias);
+
+    Ok(())
 }
@@ -0,0 +1,337 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use clap::Args;
+use serde::Serialize;
+
+use crate::database::{InstalledDatabase, InstalledPackage};
+use crate::library::sync::{self, LibraryCache};
+use crate::registry::Registry;
+use crate::version::Version;
+use crate::{human, output};
+
+/// The filters of the list subcommand. All given filters have to match.
+#[derive(Args)]
+pub struct ListFilters {
+    #[arg(long, help = "Only list explicitly installed packages")]
+    pub explicit: bool,
+    #[arg(
+        long,
+        conflicts_with = "explicit",
+        help = "Only list packages that were installed as dependencies"
+    )]
+    pub dependencies: bool,
+    #[arg(long, help = "Only list held packages")]
+    pub held: bool,
+    #[arg(long, help = "Only list packages with an upgrade available")]
+    pub upgradable: bool,
+}
+
+impl ListFilters {
+    /// matches() returns whether a listed package matches all given filters.
+    fn matches(&self, package: &ListedPackage) -> bool {
+        !((self.explicit && !package.explicit)
+            || (self.dependencies && package.explicit)
+            || (self.held && !package.held)
+            || (self.upgradable && package.upgrade.is_none()))
+    }
+}
+
+/// An installed package as it is listed. The result of the list subcommand in the JSON output is
+/// an array of them.
+#[derive(Serialize, Debug)]
+pub struct ListedPackage {
+    /// The name of the package.
+    pub name: String,
+    /// The installed version.
+    pub version: String,
+    /// The DHT key of the library the package was installed from, None for local build scripts.
+    pub library: Option<String>,
+    /// The alias of the library, if it is imported.
+    pub library_alias: Option<String>,
+    /// The time of the installation in seconds since the unix epoch.
+    pub installed_at: u64,
+    /// Whether the package was installed explicitly or as a dependency.
+    pub explicit: bool,
+    /// Whether the package is held.
+    pub held: bool,
+    /// The version requirement the package is pinned to.
+    pub pin: Option<String>,
+    /// The newer version in the synced library that satisfies the pin, if there is one.
+    pub upgrade: Option<String>,
+}
+
+/// run() runs the list subcommand which lists the installed packages.
+pub fn run(filters: ListFilters) -> Result<(), anyhow::Error> {
+    let database = InstalledDatabase::load()?;
+    let registry = Registry::load()?;
+    let mut caches = BTreeMap::new();
+
+    let mut packages = Vec::new();
+    for installed in database.packages() {
+        let package = ListedPackage {
+            name: installed.name.clone(),
+            version: installed.version.clone(),
+            library: installed.library.clone(),
+            library_alias: installed
+                .library
+                .as_ref()
+                .and_then(|library| registry.get_by_key(library))
+                .map(|library| library.alias.clone()),
+            installed_at: installed.installed_at,
+            explicit: installed.explicit,
+            held: database.is_held(&installed.name),
+            pin: database.get_pin(&installed.name).map(|pin| pin.to_string()),
+            upgrade: available_upgrade(&database, installed, &mut caches)?,
+        };
+
+        if filters.matches(&package) {
+            packages.push(package);
+        }
+    }
+
+    output::set_result(&packages)?;
+
+    if packages.is_empty() {
+        human!("No packages installed.");
+        return Ok(());
+    }
+
+    for package in packages {
+        let library = match (&package.library_alias, &package.library) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(library)) => library.clone(),
+            (None, None) => "local".to_string(),
+        };
+        let installed_at = UNIX_EPOCH + Duration::from_secs(package.installed_at);
+
+        let mut details = Vec::new();
+        if !package.explicit {
+            details.push("dependency".to_string());
+        }
+        if package.held {
+            details.push("held".to_string());
+        }
+        if let Some(pin) = &package.pin {
+            details.push(format!("pinned to {}", pin));
+        }
+        if let Some(upgrade) = &package.upgrade {
+            details.push(format!("upgrade to {} available", upgrade));
+        }
+
+        human!(
+            "{}\t{}\t{}\tinstalled {}{}",
+            package.name,
+            package.version,
+            library,
+            humantime::format_rfc3339_seconds(installed_at),
+            if details.is_empty() {
+                String::new()
+            } else {
+                format!("\t{}", details.join(", "))
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// available_upgrade() returns the version of a package in its synced library if it is newer
+/// than the installed version and satisfies the pin of the package. The caches of libraries are
+/// only read once.
+fn available_upgrade(
+    database: &InstalledDatabase,
+    installed: &InstalledPackage,
+    caches: &mut BTreeMap<String, Option<LibraryCache>>,
+) -> Result<Option<String>, anyhow::Error> {
+    let Some(library) = &installed.library else {
+        return Ok(None);
+    };
+
+    if let Entry::Vacant(entry) = caches.entry(library.clone()) {
+        entry.insert(sync::read_cache(library)?);
+    }
+    let Some(cached) = caches[library]
+        .as_ref()
+        .and_then(|cache| cache.scripts.get(&installed.name))
+    else {
+        return Ok(None);
+    };
+
+    let available = Version::parse(&cached.version);
+    if available <= Version::parse(&installed.version) {
+        return Ok(None);
+    }
+    if let Some(pin) = database.get_pin(&installed.name) {
+        if !pin.matches(&available) {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(cached.version.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::record::{LibraryHeader, LibraryIndex, ScriptLocation};
+    use crate::library::sync::CachedScript;
+    use crate::version::VersionRequirement;
+
+    const LIBRARY: &str = "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI";
+
+    fn filters(explicit: bool, dependencies: bool, held: bool, upgradable: bool) -> ListFilters {
+        ListFilters {
+            explicit,
+            dependencies,
+            held,
+            upgradable,
+        }
+    }
+
+    fn listed(explicit: bool, held: bool, upgrade: Option<&str>) -> ListedPackage {
+        ListedPackage {
+            name: "bote".to_string(),
+            version: "1.0.0".to_string(),
+            library: Some(LIBRARY.to_string()),
+            library_alias: Some("core".to_string()),
+            installed_at: 0,
+            explicit,
+            held,
+            pin: None,
+            upgrade: upgrade.map(|upgrade| upgrade.to_string()),
+        }
+    }
+
+    fn cache(scripts: &[(&str, &str)]) -> BTreeMap<String, Option<LibraryCache>> {
+        let cache = LibraryCache {
+            header: LibraryHeader {
+                name: "core".to_string(),
+                description: String::new(),
+                owner: String::new(),
+                writers: Vec::new(),
+            },
+            index: LibraryIndex::default(),
+            header_sequence_number: 0,
+            index_sequence_numbers: Vec::new(),
+            scripts: scripts
+                .iter()
+                .enumerate()
+                .map(|(subkey, (name, version))| {
+                    let script = CachedScript {
+                        version: version.to_string(),
+                        location: ScriptLocation {
+                            record: LIBRARY.to_string(),
+                            subkey: subkey as u32 + 1,
+                        },
+                        sequence_number: 0,
+                    };
+                    (name.to_string(), script)
+                })
+                .collect(),
+        };
+
+        BTreeMap::from([(LIBRARY.to_string(), Some(cache))])
+    }
+
+    fn installed(name: &str, version: &str, library: Option<&str>) -> InstalledPackage {
+        InstalledPackage::new(
+            name,
+            version,
+            library.map(|library| library.to_string()),
+            true,
+            Vec::new(),
+        )
+    }
+
+    fn upgrade(
+        database: &InstalledDatabase,
+        installed: &InstalledPackage,
+        caches: &mut BTreeMap<String, Option<LibraryCache>>,
+    ) -> Option<String> {
+        available_upgrade(database, installed, caches).unwrap()
+    }
+
+    #[test]
+    fn no_filters_match_everything() {
+        let filters = filters(false, false, false, false);
+
+        assert!(filters.matches(&listed(true, false, None)));
+        assert!(filters.matches(&listed(false, true, Some("2.0.0"))));
+    }
+
+    #[test]
+    fn filters_are_combined() {
+        let explicit_held = filters(true, false, true, false);
+        assert!(explicit_held.matches(&listed(true, true, None)));
+        assert!(!explicit_held.matches(&listed(true, false, None)));
+        assert!(!explicit_held.matches(&listed(false, true, None)));
+
+        let upgradable_dependencies = filters(false, true, false, true);
+        assert!(upgradable_dependencies.matches(&listed(false, false, Some("2.0.0"))));
+        assert!(!upgradable_dependencies.matches(&listed(false, false, None)));
+        assert!(!upgradable_dependencies.matches(&listed(true, false, Some("2.0.0"))));
+
+        let all = filters(true, false, true, true);
+        assert!(all.matches(&listed(true, true, Some("2.0.0"))));
+        assert!(!all.matches(&listed(true, true, None)));
+    }
+
+    #[test]
+    fn newer_versions_are_upgrades() {
+        let database = InstalledDatabase::default();
+        let mut caches = cache(&[("bote", "1.2.0"), ("rhai", "1.0.0")]);
+
+        let bote = installed("bote", "1.0.0", Some(LIBRARY));
+        assert_eq!(
+            upgrade(&database, &bote, &mut caches),
+            Some("1.2.0".to_string())
+        );
+
+        let rhai = installed("rhai", "1.0.0", Some(LIBRARY));
+        assert_eq!(upgrade(&database, &rhai, &mut caches), None);
+
+        let newer = installed("bote", "1.3.0", Some(LIBRARY));
+        assert_eq!(upgrade(&database, &newer, &mut caches), None);
+    }
+
+    #[test]
+    fn pins_block_upgrades() {
+        let mut database = InstalledDatabase::default();
+        let mut caches = cache(&[("bote", "2.0.0")]);
+        let bote = installed("bote", "1.0.0", Some(LIBRARY));
+
+        database.pin("bote", &VersionRequirement::parse("<2").unwrap());
+        assert_eq!(upgrade(&database, &bote, &mut caches), None);
+
+        database.pin("bote", &VersionRequirement::parse(">=1, <3").unwrap());
+        assert_eq!(
+            upgrade(&database, &bote, &mut caches),
+            Some("2.0.0".to_string())
+        );
+    }
+
+    #[test]
+    fn local_packages_have_no_upgrades() {
+        let database = InstalledDatabase::default();
+        let mut caches = BTreeMap::new();
+
+        let local = installed("bote", "1.0.0", None);
+        assert_eq!(upgrade(&database, &local, &mut caches), None);
+        assert!(caches.is_empty());
+    }
+
+    #[test]
+    fn packages_missing_from_the_cache_have_no_upgrades() {
+        let database = InstalledDatabase::default();
+        let mut caches = cache(&[("rhai", "2.0.0")]);
+
+        let removed = installed("bote", "1.0.0", Some(LIBRARY));
+        assert_eq!(upgrade(&database, &removed, &mut caches), None);
+
+        let mut caches = BTreeMap::from([(LIBRARY.to_string(), None)]);
+        let unsynced = installed("bote", "1.0.0", Some(LIBRARY));
+        assert_eq!(upgrade(&database, &unsynced, &mut caches), None);
+    }
+}
@@ -0,0 +1,43 @@
+use serde::Serialize;
+
+use crate::database::InstalledDatabase;
+use crate::version::{Version, VersionRequirement};
+use crate::{human, output};
+
+/// The result of the pin and unpin subcommands in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct PinOutput {
+    /// The name of the package.
+    pub package: String,
+    /// The version requirement the package is pinned to now.
+    pub requirement: Option<String>,
+}
+
+/// run() runs the pin subcommand which restricts the versions of a package to a version
+/// requirement.
+pub fn run(package: String, requirement: String) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    let requirement = VersionRequirement::parse(&requirement)?;
+
+    if let Some(installed) = database.get(&package) {
+        let version = Version::parse(&installed.version);
+        if !requirement.matches(&version) {
+            human!(
+                "Note: the installed version {} of {} does not satisfy {}",
+                version,
+                package,
+                requirement
+            );
+        }
+    }
+
+    database.pin(&package, &requirement);
+    database.save()?;
+    output::set_result(&PinOutput {
+        package: package.clone(),
+        requirement: Some(requirement.to_string()),
+    })?;
+    human!("{} is now pinned to {}", package, requirement);
+
+    Ok(())
+}
@@ -1,4 +1,281 @@
-/// run() runs the publish subcommand which is used to publish a package to a library.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: Implement a publishing mechanism
+use std::fs;
+use std::path::Path;
+
+use log::info;
+use serde::Serialize;
+use tempfile::tempdir;
+use veilid_core::{RoutingContext, VeilidAPI};
+
+use crate::artifact;
+use crate::blob::BlobClient;
+use crate::commands::install;
+use crate::error::Error;
+use crate::library::record::{
+    self, BinaryArtifact, Library, PackageMetadata, Publication, ScriptLocation, Writer,
+};
+use crate::library::{validate_package_name, BUILD_SCRIPT_EXTENSION};
+use crate::node::VeilidNode;
+use crate::registry::Registry;
+use crate::secrets;
+use crate::{human, output};
+
+/// The result of the publish subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct PublishOutput {
+    /// The name of the package.
+    pub package: String,
+    /// The published version.
+    pub version: String,
+    /// The DHT key of the library.
+    pub library: String,
+    /// The location of the published build script.
+    pub script: ScriptLocation,
+    /// The prebuilt artifacts of the version.
+    pub artifacts: Vec<BinaryArtifact>,
+}
+
+/// run() runs the publish subcommand which is used to publish a package to a library. If prebuilt
+/// is set, the package is built and the result is published as an artifact for this machine.
+pub async fn run(
+    node: &mut VeilidNode,
+    script: &Path,
+    library: String,
+    name: Option<String>,
+    force: bool,
+    prebuilt: bool,
+) -> Result<(), anyhow::Error> {
+    let buildscript = fs::read_to_string(script)?;
+    let name = resolve_package_name(script, name)?;
+    let script_name = script.display().to_string();
+    let metadata = install::validate_build_script(&script_name, &buildscript)?;
+
+    let imported = Registry::load()?.resolve(&library)?.clone();
+    let routing_context = node.routing_context().await?;
+    let current = record::read(&routing_context, &imported.key).await?;
+    let writer = find_writer(node.api().await?, &current, &imported.alias).await?;
+
+    // versions published by other writers can't be replaced, since only they can write their
+    // index
+    let published = current.indexes.iter().enumerate().any(|(number, index)| {
+        (number != writer.number || !force) && index.get(&name, &metadata.version).is_some()
+    });
+    if published {
+        return Err(Error::VersionExists {
+            package: name,
+            version: metadata.version,
+            library: imported.alias,
+        }
+        .into());
+    }
+
+    let mut artifacts = Vec::new();
+    if prebuilt {
+        artifacts = kept_artifacts(
+            &routing_context,
+            &current,
+            &writer,
+            &name,
+            &metadata.version,
+            &buildscript,
+        )
+        .await?;
+        artifacts.push(build_artifact(node, &script_name, &buildscript).await?);
+    }
+
+    let crypto = node.crypto().await?;
+    let location = record::publish(
+        &routing_context,
+        &crypto,
+        &imported.key,
+        &writer,
+        Publication {
+            package: &name,
+            version: &metadata.version,
+            buildscript: &buildscript,
+            metadata: PackageMetadata {
+                bote_dependencies: metadata.bote_dependencies,
+                conflicts: metadata.conflicts,
+                installed_program_dependencies: metadata.installed_program_dependencies,
+                description: metadata.description,
+                keywords: metadata.keywords,
+                license: metadata.license,
+            },
+            artifacts: artifacts.clone(),
+        },
+        force,
+    )
+    .await?;
+
+    human!(
+        "Published {} {} to {} (subkey {}).",
+        name,
+        metadata.version,
+        imported.alias,
+        location.subkey
+    );
+    for artifact in &artifacts {
+        human!(
+            "  prebuilt for {} ({} bytes)",
+            artifact.target,
+            artifact.size
+        );
+    }
+
+    output::set_result(&PublishOutput {
+        package: name,
+        version: metadata.version,
+        library: imported.key,
+        script: location,
+        artifacts,
+    })?;
+
+    Ok(())
+}
+
+/// build_artifact() builds a package into a temporary prefix and uploads the packed prefix as an
+/// artifact for the target of this machine.
+async fn build_artifact(
+    node: &mut VeilidNode,
+    script_name: &str,
+    buildscript: &str,
+) -> Result<BinaryArtifact, anyhow::Error> {
+    let blobs = BlobClient::new(node).await?;
+    let prefix = tempdir()?;
+
+    info!("Building the prebuilt artifact for {}...", artifact::TARGET);
+    let script_blobs = install::blob_client(node, buildscript).await?;
+    install::build_package(script_name, buildscript, prefix.path(), script_blobs).await?;
+
+    info!("Uploading the prebuilt artifact...");
+    let artifact = artifact::upload(&blobs, prefix.path()).await?;
+    prefix.close()?;
+
+    Ok(artifact)
+}
+
+/// kept_artifacts() returns the artifacts for other targets of a version the writer published
+/// before. They are only kept if the build script didn't change, so artifacts for several targets
+/// can be published from several machines.
+async fn kept_artifacts(
+    routing_context: &RoutingContext,
+    library: &Library,
+    writer: &Writer,
+    package: &str,
+    version: &str,
+    buildscript: &str,
+) -> Result<Vec<BinaryArtifact>, anyhow::Error> {
+    let Some(published) = library.indexes[writer.number].get(package, version) else {
+        return Ok(Vec::new());
+    };
+    let (published_script, _) = record::read_script(routing_context, &published.script).await?;
+    if published_script != buildscript {
+        return Ok(Vec::new());
+    }
+
+    Ok(published
+        .artifacts
+        .iter()
+        .filter(|published| published.target != artifact::TARGET)
+        .cloned()
+        .collect())
+}
+
+/// resolve_package_name() returns the package name given with --name or the one of the file name
+/// of the build script. The name has to be usable as a file name in the caches of other users.
+fn resolve_package_name(script: &Path, name: Option<String>) -> Result<String, Error> {
+    let name = match name {
+        Some(name) => name,
+        None => get_package_name(script)?,
+    };
+    validate_package_name(&name)?;
+
+    Ok(name)
+}
+
+/// get_package_name() returns the package name of a build script from its file name, which has
+/// to be <package>.bote.rhai.
+fn get_package_name(script: &Path) -> Result<String, Error> {
+    script
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| file_name.strip_suffix(BUILD_SCRIPT_EXTENSION))
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .ok_or_else(|| Error::Conversion {
+            from: script.display().to_string(),
+            into: format!(
+                "package name (name the script <package>{} or use --name)",
+                BUILD_SCRIPT_EXTENSION
+            ),
+        })
+}
+
+/// find_writer() returns the keypair this bote installation uses to write to a library. The
+/// owner keypair is used if the library was created here, otherwise the identity has to be one
+/// of the additional writers.
+async fn find_writer(
+    api: &VeilidAPI,
+    library: &Library,
+    alias: &str,
+) -> Result<Writer, anyhow::Error> {
+    if let Some(owner) = secrets::load_library_owner(api, &library.key).await? {
+        return Ok(Writer {
+            number: 0,
+            keypair: owner,
+        });
+    }
+
+    let identity = secrets::identity(api).await?;
+    match library.writer_number(&identity.key) {
+        Some(number) => Ok(Writer {
+            number,
+            keypair: identity,
+        }),
+        None => Err(Error::NotWriter {
+            library: alias.to_string(),
+            identity: identity.key.to_string(),
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_name_from_file_name() {
+        let name = resolve_package_name(Path::new("scripts/ripgrep.bote.rhai"), None).unwrap();
+        assert_eq!(name, "ripgrep");
+    }
+
+    #[test]
+    fn package_name_from_option() {
+        let name =
+            resolve_package_name(Path::new("build.rhai"), Some("ripgrep".to_string())).unwrap();
+        assert_eq!(name, "ripgrep");
+    }
+
+    #[test]
+    fn rejected_package_names() {
+        for name in ["", "..", "../ripgrep", "a/b", "a\\b", ".hidden"] {
+            assert!(
+                matches!(
+                    resolve_package_name(Path::new("build.rhai"), Some(name.to_string())),
+                    Err(Error::InvalidPackageName { .. })
+                ),
+                "{}",
+                name
+            );
+        }
+
+        assert!(matches!(
+            resolve_package_name(Path::new(".hidden.bote.rhai"), None),
+            Err(Error::InvalidPackageName { .. })
+        ));
+        assert!(matches!(
+            resolve_package_name(Path::new("build.rhai"), None),
+            Err(Error::Conversion { .. })
+        ));
+    }
 }
@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::database::InstalledDatabase;
+use crate::dependencies::{check_dependents, check_pins};
+use crate::error::Error;
+use crate::version::Version;
+use crate::{human, output, profile, store};
+
+/// The result of the rollback subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct RollbackOutput {
+    /// The name of the package.
+    pub package: String,
+    /// The version that was installed before.
+    pub from: String,
+    /// The version that is installed now.
+    pub to: String,
+}
+
+/// run() runs the rollback subcommand which activates a previously installed version of a
+/// package again without rebuilding it. Without a version, the most recently installed previous
+/// version is used. The version has to satisfy the pin of the package and the requirements of the
+/// installed packages that depend on it. The rollback creates a new generation of the profile.
+pub fn run(package: String, to: Option<String>) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    let installed = database.get_or_not_found(&package)?.clone();
+
+    let mut previous_versions = store::kept_versions(&package)?
+        .into_iter()
+        .filter(|manifest| manifest.package.version != installed.version);
+
+    let target = match &to {
+        Some(version) => previous_versions.find(|manifest| &manifest.package.version == version),
+        None => previous_versions.next(),
+    };
+    let Some(target) = target else {
+        return Err(Error::NotFound {
+            whats_missing: match to {
+                Some(version) => format!("kept version {} of {}", version, package),
+                None => format!("previous version of {}", package),
+            },
+        }
+        .into());
+    };
+
+    let mut restored = target.package;
+    restored.explicit = installed.explicit;
+
+    // the restored version has to respect pins and the requirements of installed dependents
+    let version = Version::parse(&restored.version);
+    check_pins(&package, &version, &restored.bote_dependencies, &database)?;
+    check_dependents(
+        &package,
+        &version,
+        database
+            .packages()
+            .filter(|installed| installed.name != package),
+    )?;
+
+    let restored_version = restored.version.clone();
+    database.insert(restored);
+    profile::commit(&database)?;
+
+    output::set_result(&RollbackOutput {
+        package: package.clone(),
+        from: installed.version.clone(),
+        to: restored_version.clone(),
+    })?;
+    human!(
+        "Rolled back {} from {} to {}",
+        package,
+        installed.version,
+        restored_version
+    );
+
+    Ok(())
+}
@@ -1,4 +1,510 @@
-/// run() runs the search subcommand which searches your imported libraries for a package.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: Implement functionality to search for a package
+use std::cmp::Ordering;
+
+use clap::{Args, ValueEnum};
+use log::warn;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::database::InstalledDatabase;
+use crate::library::record::PublishedVersion;
+use crate::library::sync;
+use crate::registry::Registry;
+use crate::version::Version;
+use crate::{human, output};
+
+/// FUZZY_THRESHOLD is the minimum similarity of a word to the query for a fuzzy match.
+const FUZZY_THRESHOLD: f64 = 0.85;
+
+/// The options of the search subcommand.
+#[derive(Args)]
+pub struct SearchOptions {
+    #[arg(
+        help = "Match names, descriptions and keywords against this (lists all packages if empty)"
+    )]
+    pub query: Option<String>,
+    #[arg(
+        long,
+        help = "Treat the query as a regex instead of matching it fuzzily"
+    )]
+    pub regex: bool,
+    #[arg(long, help = "Only search the library with this alias or DHT key")]
+    pub library: Option<String>,
+    #[arg(long, help = "Only show packages with this license")]
+    pub license: Option<String>,
+    #[arg(long, value_enum, help = "Only show packages in this state")]
+    pub state: Option<PackageState>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SortOrder::Relevance,
+        help = "How to sort the results"
+    )]
+    pub sort: SortOrder,
+}
+
+/// The state of a package on this machine.
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageState {
+    /// The package is installed from the library.
+    Installed,
+    /// The package is not installed from the library.
+    NotInstalled,
+    /// The package is installed from the library and a newer version is available.
+    Upgradable,
+}
+
+/// The order of the search results.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The best matches first.
+    Relevance,
+    /// By package name.
+    Name,
+    /// By library alias, then by package name.
+    Library,
+    /// The most recently published packages first.
+    Published,
+}
+
+/// A package that matched the query. The result of the search subcommand in the JSON output is
+/// an array of them in the requested order.
+#[derive(Serialize, Debug)]
+pub struct SearchResult {
+    /// The name of the package.
+    pub name: String,
+    /// The alias of the library.
+    pub library: String,
+    /// The DHT key of the library.
+    pub library_key: String,
+    /// The latest version.
+    pub version: String,
+    /// The index entry of the latest version.
+    pub published: PublishedVersion,
+    /// The installed version, if the package is installed from the library.
+    pub installed: Option<String>,
+    /// The state of the package on this machine.
+    pub state: PackageState,
+    /// How well the package matches the query. Higher is better.
+    pub score: f64,
+}
+
+/// package_state() returns the state of a package on this machine from its installed and its
+/// latest version.
+fn package_state(installed: Option<&str>, latest: &str) -> PackageState {
+    match installed {
+        Some(installed) if Version::parse(installed) < Version::parse(latest) => {
+            PackageState::Upgradable
+        }
+        Some(_) => PackageState::Installed,
+        None => PackageState::NotInstalled,
+    }
+}
+
+/// has_state() returns whether a search result is in a state. Upgradable packages are installed
+/// as well, so they are part of the installed packages.
+fn has_state(result: &SearchResult, state: PackageState) -> bool {
+    match state {
+        PackageState::Installed => result.installed.is_some(),
+        _ => result.state == state,
+    }
+}
+
+/// How the query is matched against packages.
+enum Matcher {
+    /// Every package matches.
+    All,
+    /// The name, description or a keyword has to match the regex.
+    Regex(Regex),
+    /// The name, description or a keyword has to contain the query or a word similar to it.
+    Fuzzy(String),
+}
+
+impl Matcher {
+    /// new() creates the matcher of a query. Matching is case insensitive.
+    fn new(query: Option<&str>, regex: bool) -> Result<Self, anyhow::Error> {
+        Ok(match query {
+            None | Some("") => Matcher::All,
+            Some(query) if regex => {
+                Matcher::Regex(RegexBuilder::new(query).case_insensitive(true).build()?)
+            }
+            Some(query) => Matcher::Fuzzy(query.to_lowercase()),
+        })
+    }
+
+    /// score() returns how well a package matches, or None if it doesn't match at all. Matches
+    /// in the name count more than matches in keywords, which count more than matches in the
+    /// description.
+    fn score(&self, name: &str, published: &PublishedVersion) -> Option<f64> {
+        let metadata = &published.metadata;
+        let fields = std::iter::once((name, 1.0))
+            .chain(
+                metadata
+                    .keywords
+                    .iter()
+                    .map(|keyword| (keyword.as_str(), 0.8)),
+            )
+            .chain([(metadata.description.as_str(), 0.6)]);
+
+        let score = match self {
+            Matcher::All => return Some(0.0),
+            Matcher::Regex(regex) => fields
+                .filter(|(field, _)| regex.is_match(field))
+                .map(|(_, weight)| weight)
+                .fold(0.0, f64::max),
+            Matcher::Fuzzy(query) => {
+                let exact = if name.to_lowercase() == *query {
+                    1.0
+                } else {
+                    0.0
+                };
+                fields
+                    .map(|(field, weight)| fuzzy_score(query, field) * weight)
+                    .fold(0.0, f64::max)
+                    + exact
+            }
+        };
+
+        (score > 0.0).then_some(score)
+    }
+}
+
+/// fuzzy_score() returns 1 if a text contains the query, the similarity of the most similar word
+/// if it is similar enough and 0 otherwise.
+fn fuzzy_score(query: &str, text: &str) -> f64 {
+    let text = text.to_lowercase();
+    if text.contains(query) {
+        return 1.0;
+    }
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| strsim::jaro_winkler(query, word))
+        .filter(|similarity| *similarity >= FUZZY_THRESHOLD)
+        .fold(0.0, f64::max)
+        * 0.9
+}
+
+/// run() runs the search subcommand which searches the synced indexes of your imported libraries
+/// for packages.
+pub fn run(options: SearchOptions) -> Result<(), anyhow::Error> {
+    let matcher = Matcher::new(options.query.as_deref(), options.regex)?;
+    let registry = Registry::load()?;
+    let database = InstalledDatabase::load()?;
+
+    let libraries = match &options.library {
+        Some(library) => vec![registry.resolve(library)?],
+        None => registry.libraries().collect(),
+    };
+
+    let mut results = Vec::new();
+    for library in libraries {
+        let Some(cache) = sync::read_cache(&library.key)? else {
+            warn!(
+                "Skipping {} because it was never synced (run \"bote library sync {}\")",
+                library.alias, library.alias
+            );
+            continue;
+        };
+
+        for (name, package) in cache.index.packages {
+            let Some((version, published)) = package.latest() else {
+                continue;
+            };
+            let Some(score) = matcher.score(&name, published) else {
+                continue;
+            };
+
+            if let Some(license) = &options.license {
+                if !published
+                    .metadata
+                    .license
+                    .as_ref()
+                    .is_some_and(|published| published.eq_ignore_ascii_case(license))
+                {
+                    continue;
+                }
+            }
+
+            let installed = database
+                .get(&name)
+                .filter(|installed| installed.library.as_ref() == Some(&library.key))
+                .map(|installed| installed.version.clone());
+
+            results.push(SearchResult {
+                version: version.to_string(),
+                published: published.clone(),
+                name,
+                library: library.alias.clone(),
+                library_key: library.key.clone(),
+                state: package_state(installed.as_deref(), version),
+                installed,
+                score,
+            });
+        }
+    }
+
+    if let Some(state) = options.state {
+        results.retain(|result| has_state(result, state));
+    }
+
+    results.sort_by(|a, b| compare(options.sort, a, b));
+    output::set_result(&results)?;
+
+    if results.is_empty() {
+        human!("No packages found.");
+        return Ok(());
+    }
+
+    for result in &results {
+        let state = match result.state {
+            PackageState::Installed => "\tinstalled".to_string(),
+            PackageState::Upgradable => format!(
+                "\tinstalled {}, update available",
+                result.installed.as_deref().unwrap_or_default()
+            ),
+            PackageState::NotInstalled => String::new(),
+        };
+
+        human!(
+            "{}/{}\t{}{}",
+            result.library,
+            result.name,
+            result.version,
+            state
+        );
+        if !result.published.metadata.description.is_empty() {
+            human!("\t{}", result.published.metadata.description);
+        }
+    }
+
+    Ok(())
+}
+
+/// compare() compares two search results in a sort order. Ties are broken by name and library.
+fn compare(sort: SortOrder, a: &SearchResult, b: &SearchResult) -> Ordering {
+    let by_name = a.name.cmp(&b.name).then_with(|| a.library.cmp(&b.library));
+
+    match sort {
+        SortOrder::Relevance => b.score.total_cmp(&a.score).then(by_name),
+        SortOrder::Name => by_name,
+        SortOrder::Library => a.library.cmp(&b.library).then_with(|| a.name.cmp(&b.name)),
+        SortOrder::Published => b
+            .published
+            .published_at
+            .cmp(&a.published.published_at)
+            .then(by_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::record::{PackageMetadata, ScriptLocation};
+
+    fn published(description: &str, keywords: &[&str], published_at: u64) -> PublishedVersion {
+        PublishedVersion {
+            script: ScriptLocation {
+                record: "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI".to_string(),
+                subkey: 1,
+            },
+            metadata: PackageMetadata {
+                bote_dependencies: Vec::new(),
+                conflicts: Vec::new(),
+                installed_program_dependencies: Vec::new(),
+                description: description.to_string(),
+                keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+                license: None,
+            },
+            published_at,
+            signature: None,
+            artifacts: Vec::new(),
+        }
+    }
+
+    fn result(name: &str, library: &str, score: f64, published_at: u64) -> SearchResult {
+        SearchResult {
+            name: name.to_string(),
+            library: library.to_string(),
+            library_key: "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI".to_string(),
+            version: "1.0.0".to_string(),
+            published: published("", &[], published_at),
+            installed: None,
+            state: PackageState::NotInstalled,
+            score,
+        }
+    }
+
+    fn installed(version: &str) -> SearchResult {
+        SearchResult {
+            installed: Some(version.to_string()),
+            state: package_state(Some(version), "1.0.0"),
+            ..result("bote", "core", 1.0, 0)
+        }
+    }
+
+    fn score(query: &str, regex: bool, name: &str, published: &PublishedVersion) -> Option<f64> {
+        Matcher::new(Some(query), regex)
+            .unwrap()
+            .score(name, published)
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_substrings() {
+        assert_eq!(fuzzy_score("grep", "ripgrep"), 1.0);
+        assert_eq!(fuzzy_score("grep", "A line-oriented search tool"), 0.0);
+
+        let similar = fuzzy_score("ripgrap", "the ripgrep tool");
+        assert!(similar > 0.0 && similar < 1.0, "{similar}");
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_words_below_the_threshold() {
+        assert!(strsim::jaro_winkler("rhai", "rust") > 0.0);
+        assert!(strsim::jaro_winkler("rhai", "rust") < FUZZY_THRESHOLD);
+        assert_eq!(fuzzy_score("rhai", "written in rust"), 0.0);
+    }
+
+    #[test]
+    fn matcher_all_matches_everything() {
+        let matcher = Matcher::new(None, false).unwrap();
+        assert_eq!(matcher.score("bote", &published("", &[], 0)), Some(0.0));
+
+        let matcher = Matcher::new(Some(""), true).unwrap();
+        assert_eq!(matcher.score("bote", &published("", &[], 0)), Some(0.0));
+    }
+
+    #[test]
+    fn matcher_ranks_exact_names_first() {
+        let published = published("", &[], 0);
+
+        let exact = score("Bote", false, "bote", &published).unwrap();
+        let substring = score("bot", false, "bote", &published).unwrap();
+
+        assert_eq!(exact, 2.0);
+        assert_eq!(substring, 1.0);
+    }
+
+    #[test]
+    fn matcher_weights_fields() {
+        let published = published("A package manager on veilid", &["dht"], 0);
+
+        assert_eq!(score("bote", false, "bote-cli", &published), Some(1.0));
+        assert_eq!(score("dht", false, "bote", &published), Some(0.8));
+        assert_eq!(score("veilid", false, "bote", &published), Some(0.6));
+        assert_eq!(score("rhai", false, "bote", &published), None);
+    }
+
+    #[test]
+    fn matcher_uses_the_fuzzy_threshold() {
+        let published = published("", &[], 0);
+
+        assert!(score("ripgrap", false, "ripgrep", &published).is_some());
+        assert_eq!(score("zstd", false, "ripgrep", &published), None);
+    }
+
+    #[test]
+    fn matcher_matches_regexes() {
+        let published = published("A package manager on veilid", &["dht"], 0);
+
+        assert_eq!(score("^BO", true, "bote", &published), Some(1.0));
+        assert_eq!(score("^d.t$", true, "bote", &published), Some(0.8));
+        assert_eq!(score("manager", true, "bote", &published), Some(0.6));
+        assert_eq!(score("^manager", true, "bote", &published), None);
+        assert!(Matcher::new(Some("("), true).is_err());
+    }
+
+    #[test]
+    fn installed_state_includes_upgradable_packages() {
+        let current = installed("1.0.0");
+        let outdated = installed("0.9.0");
+        let missing = result("bote", "core", 1.0, 0);
+
+        assert!(has_state(&current, PackageState::Installed));
+        assert!(has_state(&outdated, PackageState::Installed));
+        assert!(!has_state(&missing, PackageState::Installed));
+
+        assert!(!has_state(&current, PackageState::Upgradable));
+        assert!(has_state(&outdated, PackageState::Upgradable));
+        assert!(!has_state(&missing, PackageState::Upgradable));
+
+        assert!(has_state(&missing, PackageState::NotInstalled));
+        assert!(!has_state(&outdated, PackageState::NotInstalled));
+    }
+
+    fn sorted(sort: SortOrder, mut results: Vec<SearchResult>) -> Vec<(String, String)> {
+        results.sort_by(|a, b| compare(sort, a, b));
+        results
+            .into_iter()
+            .map(|result| (result.library, result.name))
+            .collect()
+    }
+
+    fn results() -> Vec<SearchResult> {
+        vec![
+            result("ripgrep", "core", 1.5, 300),
+            result("bote", "extra", 1.0, 100),
+            result("bote", "core", 1.0, 200),
+            result("amp", "extra", 2.0, 200),
+        ]
+    }
+
+    fn names(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(library, name)| (library.to_string(), name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn compare_by_relevance() {
+        assert_eq!(
+            sorted(SortOrder::Relevance, results()),
+            names(&[
+                ("extra", "amp"),
+                ("core", "ripgrep"),
+                ("core", "bote"),
+                ("extra", "bote")
+            ])
+        );
+    }
+
+    #[test]
+    fn compare_by_name() {
+        assert_eq!(
+            sorted(SortOrder::Name, results()),
+            names(&[
+                ("extra", "amp"),
+                ("core", "bote"),
+                ("extra", "bote"),
+                ("core", "ripgrep")
+            ])
+        );
+    }
+
+    #[test]
+    fn compare_by_library() {
+        assert_eq!(
+            sorted(SortOrder::Library, results()),
+            names(&[
+                ("core", "bote"),
+                ("core", "ripgrep"),
+                ("extra", "amp"),
+                ("extra", "bote")
+            ])
+        );
+    }
+
+    #[test]
+    fn compare_by_published() {
+        assert_eq!(
+            sorted(SortOrder::Published, results()),
+            names(&[
+                ("core", "ripgrep"),
+                ("extra", "amp"),
+                ("core", "bote"),
+                ("extra", "bote")
+            ])
+        );
+    }
 }
@@ -0,0 +1,63 @@
+use log::warn;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::node::{is_ready_for_dht, VeilidNode};
+use crate::status::attachment_state_name;
+use crate::{human, output};
+
+/// The result of the status subcommand in the JSON output.
+#[derive(Serialize, Debug)]
+pub struct StatusOutput {
+    /// The node IDs of the veilid node.
+    pub node_ids: Vec<String>,
+    /// The attachment state, e.g. "weak" or "fully attached".
+    pub attachment: String,
+    /// The number of peers the node is connected to.
+    pub peers: usize,
+    /// Whether the node can use the DHT.
+    pub dht_ready: bool,
+}
+
+/// run() runs the status subcommand which attaches the veilid node to the network and prints its
+/// status. If the attachment times out, the state veilid reached until then is printed.
+pub async fn run(node: &mut VeilidNode) -> Result<(), anyhow::Error> {
+    let state = match node.api().await {
+        Ok(api) => api.get_state().await?,
+        Err(e) if matches!(e.downcast_ref(), Some(Error::AttachmentTimeout { .. })) => {
+            warn!("{}, showing the current state", e);
+            node.start().await?.get_state().await?
+        }
+        Err(e) => return Err(e),
+    };
+    let status = node.status();
+
+    let node_ids: Vec<String> = state
+        .config
+        .config
+        .network
+        .routing_table
+        .node_id
+        .iter()
+        .map(|node_id| node_id.to_string())
+        .collect();
+    let dht_ready =
+        is_ready_for_dht(&state.attachment.state) && state.attachment.public_internet_ready;
+
+    human!("Node ID:      {}", node_ids.join(", "));
+    human!(
+        "Attachment:   {}",
+        attachment_state_name(&state.attachment.state)
+    );
+    human!("Peers:        {}", status.peer_count);
+    human!("DHT ready:    {}", if dht_ready { "yes" } else { "no" });
+
+    output::set_result(&StatusOutput {
+        node_ids,
+        attachment: attachment_state_name(&state.attachment.state).to_string(),
+        peers: status.peer_count,
+        dht_ready,
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use log::warn;
+
+use crate::commands::hold::HoldOutput;
+use crate::database::InstalledDatabase;
+use crate::{human, output};
+
+/// run() runs the unhold subcommand which allows a held package to be upgraded again.
+pub fn run(package: String) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    output::set_result(&HoldOutput {
+        package: package.clone(),
+        held: false,
+    })?;
+
+    if !database.unhold(&package) {
+        warn!("{} is not held", package);
+        return Ok(());
+    }
+
+    database.save()?;
+    human!("{} is no longer held", package);
+
+    Ok(())
+}
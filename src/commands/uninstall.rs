@@ -1,4 +1,39 @@
-/// run() runs the uninstall subcommand, which is used to uninstall a package.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: Implement an uninstall functionality
+use anyhow::bail;
+
+use crate::database::InstalledDatabase;
+use crate::error::Error;
+use crate::{human, output, profile};
+
+/// run() runs the uninstall subcommand, which is used to uninstall a package. The package is
+/// removed from a new generation of the profile, so it stays in the store until it is garbage
+/// collected and the uninstall can be undone by switching back to the previous generation. The
+/// database entry of the uninstalled package is the result in the JSON output.
+pub fn run(package: String) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    let uninstalled = database.get_or_not_found(&package)?.clone();
+
+    let dependents: Vec<String> = database
+        .packages()
+        .filter(|installed| {
+            installed
+                .bote_dependencies
+                .iter()
+                .any(|dependency| dependency.name == package)
+        })
+        .map(|installed| installed.name.clone())
+        .collect();
+    if !dependents.is_empty() {
+        bail!(Error::RequiredBy {
+            package,
+            dependents,
+        });
+    }
+
+    database.remove(&package);
+    profile::commit(&database)?;
+
+    output::set_result(&uninstalled)?;
+    human!("Uninstalled {}", package);
+
+    Ok(())
 }
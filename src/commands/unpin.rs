@@ -0,0 +1,24 @@
+use log::warn;
+
+use crate::commands::pin::PinOutput;
+use crate::database::InstalledDatabase;
+use crate::{human, output};
+
+/// run() runs the unpin subcommand which removes the version pin of a package.
+pub fn run(package: String) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    output::set_result(&PinOutput {
+        package: package.clone(),
+        requirement: None,
+    })?;
+
+    match database.unpin(&package) {
+        Some(requirement) => {
+            database.save()?;
+            human!("{} is no longer pinned to {}", package, requirement);
+        }
+        None => warn!("{} is not pinned", package),
+    }
+
+    Ok(())
+}
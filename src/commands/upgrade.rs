@@ -1,4 +1,309 @@
-/// run() runs the upgrade subcommand which updates all installed packages.
-pub fn run() -> Result<(), anyhow::Error> {
-    todo!() // TODO: Implement functionality to update all installed packages.
+use std::collections::BTreeMap;
+
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::commands::install::{self, PlannedDependency};
+use crate::database::InstalledDatabase;
+use crate::dependencies::{check_dependents, check_pins, sort_by_dependencies};
+use crate::error::Error;
+use crate::node::VeilidNode;
+use crate::package::PackageReference;
+use crate::prompt::confirm;
+use crate::registry::Registry;
+use crate::version::Version;
+use crate::{human, library, output, profile};
+
+/// The result of the upgrade subcommand in the JSON output.
+#[derive(Serialize, Debug, Default)]
+pub struct UpgradeOutput {
+    /// The planned upgrades in the order they are installed.
+    pub upgrades: Vec<UpgradeEntry>,
+    /// The missing bote dependencies of the new versions, which are installed before the
+    /// upgrades.
+    pub dependencies: Vec<String>,
+    /// Whether the upgrades were installed. This is false for dry runs and aborted upgrades.
+    pub upgraded: bool,
+}
+
+/// A planned upgrade of a package.
+#[derive(Serialize, Debug)]
+pub struct UpgradeEntry {
+    /// The name of the package.
+    pub package: String,
+    /// The installed version.
+    pub from: String,
+    /// The version the package is upgraded to.
+    pub to: String,
+    /// Whether the user trusts the maintainers of the library of the package.
+    pub trusted: bool,
+}
+
+/// A package that will be upgraded.
+struct PlannedUpgrade {
+    name: String,
+    library: String,
+    installed_version: Version,
+    available_version: Version,
+    buildscript: String,
+    bote_dependencies: Vec<PackageReference>,
+    explicit: bool,
+    trusted: bool,
+}
+
+/// run() runs the upgrade subcommand which updates all installed packages. If packages are
+/// given, only those packages are upgraded. Held packages and upgrades to versions outside of a
+/// pin are skipped. Missing bote dependencies of the new versions are installed, and nothing is
+/// installed if an installed package doesn't accept a new version. Build scripts are verified
+/// before they are compiled, unless insecure is set. Upgrades from libraries the user doesn't
+/// trust are only installed after confirmation, so they fail with yes unless insecure is set.
+/// Prebuilt artifacts are used where available, unless build_from_source is set.
+pub async fn run(
+    node: &mut VeilidNode,
+    packages: Vec<String>,
+    dry_run: bool,
+    yes: bool,
+    insecure: bool,
+    build_from_source: bool,
+) -> Result<(), anyhow::Error> {
+    let mut database = InstalledDatabase::load()?;
+    let registry = Registry::load()?;
+
+    let candidates = if packages.is_empty() {
+        database.packages().collect::<Vec<_>>()
+    } else {
+        packages
+            .iter()
+            .map(|name| database.get_or_not_found(name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut plan = BTreeMap::new();
+
+    for installed in candidates {
+        let Some(library) = &installed.library else {
+            info!(
+                "Skipping {} because it was installed from a local build script",
+                installed.name
+            );
+            continue;
+        };
+
+        if database.is_held(&installed.name) {
+            human!(
+                "Skipping {} because it is held (run \"bote unhold {}\" to upgrade it)",
+                installed.name,
+                installed.name
+            );
+            continue;
+        }
+
+        let buildscript = library::fetch_build_script(library, &installed.name)?;
+        install::verify_build_script(node, library, &installed.name, &buildscript, insecure)
+            .await?;
+        let metadata =
+            install::read_metadata(&library::build_script_name(&installed.name), &buildscript)?;
+
+        let installed_version = Version::parse(&installed.version);
+        let available_version = Version::parse(&metadata.version);
+
+        if available_version <= installed_version {
+            info!("{} is up to date ({})", installed.name, installed_version);
+            continue;
+        }
+
+        if let Some(pin) = database.get_pin(&installed.name) {
+            if !pin.matches(&available_version) {
+                human!(
+                    "Skipping {} {} because it is pinned to {}",
+                    installed.name,
+                    available_version,
+                    pin
+                );
+                continue;
+            }
+        }
+
+        plan.insert(
+            installed.name.clone(),
+            PlannedUpgrade {
+                name: installed.name.clone(),
+                library: library.clone(),
+                installed_version,
+                available_version,
+                buildscript,
+                bote_dependencies: metadata.bote_dependencies,
+                explicit: installed.explicit,
+                trusted: registry.is_trusted(library),
+            },
+        );
+    }
+
+    if plan.is_empty() {
+        output::set_result(&UpgradeOutput::default())?;
+        human!("All packages are up to date.");
+        return Ok(());
+    }
+
+    // the requirements have to hold for the versions every package has after the upgrade
+    let mut versions = database.versions();
+    for upgrade in plan.values() {
+        versions.insert(upgrade.name.clone(), upgrade.available_version.clone());
+    }
+    for upgrade in plan.values() {
+        check_pins(
+            &upgrade.name,
+            &upgrade.available_version,
+            &upgrade.bote_dependencies,
+            &database,
+        )?;
+        check_dependents(
+            &upgrade.name,
+            &upgrade.available_version,
+            database
+                .packages()
+                .filter(|installed| !plan.contains_key(&installed.name)),
+        )?;
+    }
+
+    let dependents: Vec<(&str, &[PackageReference])> = plan
+        .values()
+        .map(|upgrade| (upgrade.name.as_str(), upgrade.bote_dependencies.as_slice()))
+        .collect();
+    let missing =
+        install::resolve_dependencies(node, &database, &versions, &dependents, insecure).await?;
+    for dependency in missing.values() {
+        check_pins(
+            &dependency.name,
+            &dependency.version,
+            &dependency.bote_dependencies,
+            &database,
+        )?;
+    }
+
+    let dependencies: BTreeMap<String, Vec<String>> = plan
+        .values()
+        .map(|upgrade| {
+            (
+                upgrade.name.clone(),
+                install::reference_names(&upgrade.bote_dependencies),
+            )
+        })
+        .chain(missing.values().map(|dependency| {
+            (
+                dependency.name.clone(),
+                install::reference_names(&dependency.bote_dependencies),
+            )
+        }))
+        .collect();
+    let order = sort_by_dependencies(&dependencies)?;
+
+    let mut result = UpgradeOutput {
+        upgrades: order
+            .iter()
+            .filter(|name| plan.contains_key(*name))
+            .map(|name| UpgradeEntry {
+                package: name.clone(),
+                from: plan[name].installed_version.to_string(),
+                to: plan[name].available_version.to_string(),
+                trusted: plan[name].trusted,
+            })
+            .collect(),
+        dependencies: missing.keys().cloned().collect(),
+        upgraded: false,
+    };
+    output::set_result(&result)?;
+
+    human!("The following packages will be upgraded:");
+    for upgrade in &result.upgrades {
+        let untrusted = if upgrade.trusted { "" } else { " (untrusted)" };
+        human!(
+            "  {} {} -> {}{}",
+            upgrade.package,
+            upgrade.from,
+            upgrade.to,
+            untrusted
+        );
+    }
+
+    if !result.dependencies.is_empty() {
+        human!("The following missing dependencies will be installed:");
+        for dependency in &result.dependencies {
+            human!("  {} {}", dependency, missing[dependency].version);
+        }
+    }
+
+    let untrusted = plan.values().find(|upgrade| !upgrade.trusted);
+    if untrusted.is_some() {
+        if insecure {
+            warn!("Upgrading packages from untrusted libraries because of --insecure");
+        } else {
+            human!("Untrusted packages come from libraries whose maintainers you don't trust.");
+        }
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if let Some(upgrade) = untrusted.filter(|_| yes && !insecure) {
+        return Err(Error::UntrustedLibrary {
+            library: registry
+                .get_by_key(&upgrade.library)
+                .map(|library| library.alias.clone())
+                .unwrap_or_else(|| upgrade.library.clone()),
+        }
+        .into());
+    }
+
+    if !yes && !confirm("Do you want to continue?")? {
+        warn!("Upgrade aborted");
+        return Ok(());
+    }
+
+    let upgraded =
+        upgrade_packages(node, &mut database, plan, missing, order, build_from_source).await;
+
+    // commit the finished upgrades even if one of them failed
+    profile::commit(&database)?;
+
+    upgraded?;
+    result.upgraded = true;
+    output::set_result(&result)?;
+
+    Ok(())
+}
+
+/// upgrade_packages() installs the missing dependencies of the upgrades and then the planned
+/// upgrades in the given order.
+async fn upgrade_packages(
+    node: &mut VeilidNode,
+    database: &mut InstalledDatabase,
+    mut plan: BTreeMap<String, PlannedUpgrade>,
+    missing: BTreeMap<String, PlannedDependency>,
+    order: Vec<String>,
+    build_from_source: bool,
+) -> Result<(), anyhow::Error> {
+    install::install_dependencies(node, database, missing, order.clone(), build_from_source)
+        .await?;
+
+    for name in order {
+        let Some(upgrade) = plan.remove(&name) else {
+            continue;
+        };
+
+        info!("Upgrading {}...", upgrade.name);
+        install::install_from_library(
+            node,
+            database,
+            &upgrade.name,
+            &upgrade.library,
+            &upgrade.buildscript,
+            upgrade.explicit,
+            build_from_source,
+        )
+        .await?;
+    }
+
+    Ok(())
 }
@@ -1,153 +1,428 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
 use home;
-use veilid_core::{ConfigCallbackReturn, FourCC, TypedKeyGroup, TypedSecretGroup};
+use veilid_core::{ConfigCallback, ConfigCallbackReturn, FourCC, TypedKeyGroup, TypedSecretGroup};
 
 use crate::error::Error;
 
-/// config_callback() is used by veilid to generate a configuration for veilid at the startup.
-pub fn config_callback(key: String) -> ConfigCallbackReturn {
-    // HACK: This contains a lot of magic values. Maybe I should write a config file in JSON
-    // instead and import it at runtime? (This code was basically copied from vldpipe: https://gitlab.com/vatueil/vldpipe/-/blob/main/src/config.rs?ref_type=heads)
-    match key.as_str() {
-        "program_name" => Ok(Box::new(String::from("bote"))),
-        "namespace" => Ok(Box::<String>::default()),
-        "capabilities.disable" => Ok(Box::<Vec<FourCC>>::default()),
-        "table_store.directory" => Ok(Box::new(get_veilid_table_store_path()?)),
-        "table_store.delete" => Ok(Box::new(false)),
-        "block_store.directory" => Ok(Box::new(get_veilid_block_store_path()?)),
-        "block_store.delete" => Ok(Box::new(false)),
-        "protected_store.allow_insecure_fallback" => Ok(Box::new(true)),
-        "protected_store.always_use_insecure_storage" => Ok(Box::new(false)),
-        "protected_store.directory" => Ok(Box::new(get_veilid_protected_store_path()?)),
-        "protected_store.delete" => Ok(Box::new(false)),
-        "protected_store.device_encryption_key_password" => Ok(Box::new("".to_owned())),
-        "protected_store.new_device_encryption_key_password" => {
-            Ok(Box::new(Option::<String>::None))
+/// A value of a veilid setting that can be changed in the configuration file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VeilidSetting {
+    Bool(bool),
+    U8(u8),
+    U32(u32),
+    OptionalU32(Option<u32>),
+    Text(String),
+    OptionalText(Option<String>),
+    TextList(Vec<String>),
+}
+
+impl VeilidSetting {
+    /// type_name() returns a human readable name of the type of the setting.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "a boolean",
+            Self::U8(_) => "an integer between 0 and 255",
+            Self::U32(_) => "an integer between 0 and 4294967295",
+            Self::OptionalU32(_) => "an integer between 0 and 4294967295 or null",
+            Self::Text(_) => "a string",
+            Self::OptionalText(_) => "a string or null",
+            Self::TextList(_) => "a list of strings",
+        }
+    }
+
+    /// with_json_value() parses a JSON value into a setting of the same type. None is returned if
+    /// the value has a different type.
+    pub fn with_json_value(&self, value: &serde_json::Value) -> Option<Self> {
+        let as_u32 = |value: &serde_json::Value| value.as_u64().and_then(|v| u32::try_from(v).ok());
+
+        match self {
+            Self::Bool(_) => value.as_bool().map(Self::Bool),
+            Self::U8(_) => value
+                .as_u64()
+                .and_then(|v| u8::try_from(v).ok())
+                .map(Self::U8),
+            Self::U32(_) => as_u32(value).map(Self::U32),
+            Self::OptionalU32(_) if value.is_null() => Some(Self::OptionalU32(None)),
+            Self::OptionalU32(_) => as_u32(value).map(|v| Self::OptionalU32(Some(v))),
+            Self::Text(_) => value.as_str().map(|v| Self::Text(v.to_string())),
+            Self::OptionalText(_) if value.is_null() => Some(Self::OptionalText(None)),
+            Self::OptionalText(_) => value
+                .as_str()
+                .map(|v| Self::OptionalText(Some(v.to_string()))),
+            Self::TextList(_) => value
+                .as_array()
+                .and_then(|values| {
+                    values
+                        .iter()
+                        .map(|v| v.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .map(Self::TextList),
+        }
+    }
+
+    /// to_json_value() converts the setting into the JSON value used in the configuration file.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        match self {
+            Self::Bool(v) => serde_json::json!(v),
+            Self::U8(v) => serde_json::json!(v),
+            Self::U32(v) => serde_json::json!(v),
+            Self::OptionalU32(v) => serde_json::json!(v),
+            Self::Text(v) => serde_json::json!(v),
+            Self::OptionalText(v) => serde_json::json!(v),
+            Self::TextList(v) => serde_json::json!(v),
+        }
+    }
+
+    /// into_config_value() boxes the setting in the type veilid expects for it.
+    fn into_config_value(self) -> Box<dyn Any + Send> {
+        match self {
+            Self::Bool(v) => Box::new(v),
+            Self::U8(v) => Box::new(v),
+            Self::U32(v) => Box::new(v),
+            Self::OptionalU32(v) => Box::new(v),
+            Self::Text(v) => Box::new(v),
+            Self::OptionalText(v) => Box::new(v),
+            Self::TextList(v) => Box::new(v),
         }
-        "network.connection_initial_timeout_ms" => Ok(Box::new(2_000u32)),
-        "network.connection_inactivity_timeout_ms" => Ok(Box::new(60_000u32)),
-        "network.max_connections_per_ip4" => Ok(Box::new(8u32)),
-        "network.max_connections_per_ip6_prefix" => Ok(Box::new(8u32)),
-        "network.max_connections_per_ip6_prefix_size" => Ok(Box::new(56u32)),
-        "network.max_connection_frequency_per_min" => Ok(Box::new(8u32)),
-        "network.client_whitelist_timeout_ms" => Ok(Box::new(300_000u32)),
-        "network.reverse_connection_receipt_time_ms" => Ok(Box::new(5_000u32)),
-        "network.hole_punch_receipt_time_ms" => Ok(Box::new(5_000u32)),
-        "network.network_key_password" => Ok(Box::new(Option::<String>::None)),
-        "network.routing_table.node_id" => Ok(Box::new(TypedKeyGroup::new())),
-        "network.routing_table.node_id_secret" => Ok(Box::new(TypedSecretGroup::new())),
-        "network.routing_table.bootstrap" => Ok(Box::new(vec!["bootstrap.veilid.net".to_string()])),
-        "network.routing_table.limit_over_attached" => Ok(Box::new(64u32)),
-        "network.routing_table.limit_fully_attached" => Ok(Box::new(32u32)),
-        "network.routing_table.limit_attached_strong" => Ok(Box::new(16u32)),
-        "network.routing_table.limit_attached_good" => Ok(Box::new(8u32)),
-        "network.routing_table.limit_attached_weak" => Ok(Box::new(4u32)),
-        "network.rpc.concurrency" => Ok(Box::new(2u32)),
-        "network.rpc.queue_size" => Ok(Box::new(1024u32)),
-        "network.rpc.max_timestamp_behind_ms" => Ok(Box::new(Some(10_000u32))),
-        "network.rpc.max_timestamp_ahead_ms" => Ok(Box::new(Some(10_000u32))),
-        "network.rpc.timeout_ms" => Ok(Box::new(5_000u32)),
-        "network.rpc.max_route_hop_count" => Ok(Box::new(4u8)),
-        "network.rpc.default_route_hop_count" => Ok(Box::new(1u8)),
-        "network.dht.max_find_node_count" => Ok(Box::new(20u32)),
-        "network.dht.resolve_node_timeout_ms" => Ok(Box::new(10_000u32)),
-        "network.dht.resolve_node_count" => Ok(Box::new(1u32)),
-        "network.dht.resolve_node_fanout" => Ok(Box::new(4u32)),
-        "network.dht.get_value_timeout_ms" => Ok(Box::new(10_000u32)),
-        "network.dht.get_value_count" => Ok(Box::new(3u32)),
-        "network.dht.get_value_fanout" => Ok(Box::new(4u32)),
-        "network.dht.set_value_timeout_ms" => Ok(Box::new(10_000u32)),
-        "network.dht.set_value_count" => Ok(Box::new(5u32)),
-        "network.dht.set_value_fanout" => Ok(Box::new(4u32)),
-        "network.dht.min_peer_count" => Ok(Box::new(20u32)),
-        "network.dht.min_peer_refresh_time_ms" => Ok(Box::new(60_000u32)),
-        "network.dht.validate_dial_info_receipt_time_ms" => Ok(Box::new(5_000u32)),
-        "network.dht.local_subkey_cache_size" => Ok(Box::new(128u32)),
-        "network.dht.local_max_subkey_cache_memory_mb" => Ok(Box::new(256u32)),
-        "network.dht.remote_subkey_cache_size" => Ok(Box::new(1024u32)),
-        "network.dht.remote_max_records" => Ok(Box::new(4096u32)),
-        "network.dht.remote_max_subkey_cache_memory_mb" => Ok(Box::new(64u32)),
-        "network.dht.remote_max_storage_space_mb" => Ok(Box::new(64u32)),
-        "network.upnp" => Ok(Box::new(true)),
-        "network.detect_address_changes" => Ok(Box::new(true)),
-        "network.restricted_nat_retries" => Ok(Box::new(3u32)),
-        "network.tls.certificate_path" => Ok(Box::new(get_veilid_certfile_path()?)),
-        "network.tls.private_key_path" => Ok(Box::new(get_veilid_keyfile_path()?)),
-        "network.tls.connection_initial_timeout_ms" => Ok(Box::new(2_000u32)),
-        "network.application.https.enabled" => Ok(Box::new(false)),
-        "network.application.https.listen_address" => Ok(Box::new("".to_owned())),
-        "network.application.https.path" => Ok(Box::new(String::from("app"))),
-        "network.application.https.url" => Ok(Box::new(Option::<String>::None)),
-        "network.application.http.enabled" => Ok(Box::new(false)),
-        "network.application.http.listen_address" => Ok(Box::new("".to_owned())),
-        "network.application.http.path" => Ok(Box::new(String::from("app"))),
-        "network.application.http.url" => Ok(Box::new(Option::<String>::None)),
-        "network.protocol.udp.enabled" => Ok(Box::new(true)),
-        "network.protocol.udp.socket_pool_size" => Ok(Box::new(16u32)),
-        "network.protocol.udp.listen_address" => Ok(Box::new("".to_owned())),
-        "network.protocol.udp.public_address" => Ok(Box::new(Option::<String>::None)),
-        "network.protocol.tcp.connect" => Ok(Box::new(true)),
-        "network.protocol.tcp.listen" => Ok(Box::new(true)),
-        "network.protocol.tcp.max_connections" => Ok(Box::new(32u32)),
-        "network.protocol.tcp.listen_address" => Ok(Box::new("".to_owned())),
-        "network.protocol.tcp.public_address" => Ok(Box::new(Option::<String>::None)),
-        "network.protocol.ws.connect" => Ok(Box::new(false)),
-        "network.protocol.ws.listen" => Ok(Box::new(false)),
-        "network.protocol.ws.max_connections" => Ok(Box::new(16u32)),
-        "network.protocol.ws.listen_address" => Ok(Box::new("".to_owned())),
-        "network.protocol.ws.path" => Ok(Box::new(String::from("ws"))),
-        "network.protocol.ws.url" => Ok(Box::new(Option::<String>::None)),
-        "network.protocol.wss.connect" => Ok(Box::new(false)),
-        "network.protocol.wss.listen" => Ok(Box::new(false)),
-        "network.protocol.wss.max_connections" => Ok(Box::new(16u32)),
-        "network.protocol.wss.listen_address" => Ok(Box::new("".to_owned())),
-        "network.protocol.wss.path" => Ok(Box::new(String::from("ws"))),
-        "network.protocol.wss.url" => Ok(Box::new(Option::<String>::None)),
-        _ => Err(Error::NotFound { whats_missing: key }.into()),
     }
 }
 
-/// get_app_directory() returns the directory where bote keeps files (~/.bote)
-pub fn get_app_directory() -> Result<String, Error> {
-    match home::home_dir() {
-        Some(path) => {
-            if let Some(path_string) = path.to_str() {
-                return Ok(path_string.to_string() + "/.bote");
-            }
-            Err(Error::Conversion {
-                from: "PathBuf".to_string(),
-                into: "&str".to_string(),
-            })
+/// default_veilid_settings() returns the built-in defaults of all veilid settings that can be
+/// changed in the configuration file. The keys are the ones veilid passes to config_callback().
+pub fn default_veilid_settings() -> Result<BTreeMap<String, VeilidSetting>, Error> {
+    // These defaults were basically copied from vldpipe: https://gitlab.com/vatueil/vldpipe/-/blob/main/src/config.rs?ref_type=heads
+    use VeilidSetting::*;
+
+    let settings = [
+        (
+            "table_store.directory",
+            Text(path_to_string(get_veilid_table_store_path()?)?),
+        ),
+        ("table_store.delete", Bool(false)),
+        (
+            "block_store.directory",
+            Text(path_to_string(get_veilid_block_store_path()?)?),
+        ),
+        ("block_store.delete", Bool(false)),
+        ("protected_store.allow_insecure_fallback", Bool(true)),
+        ("protected_store.always_use_insecure_storage", Bool(false)),
+        (
+            "protected_store.directory",
+            Text(path_to_string(get_veilid_protected_store_path()?)?),
+        ),
+        ("protected_store.delete", Bool(false)),
+        (
+            "protected_store.device_encryption_key_password",
+            Text(String::new()),
+        ),
+        (
+            "protected_store.new_device_encryption_key_password",
+            OptionalText(None),
+        ),
+        ("network.connection_initial_timeout_ms", U32(2_000u32)),
+        ("network.connection_inactivity_timeout_ms", U32(60_000u32)),
+        ("network.max_connections_per_ip4", U32(8u32)),
+        ("network.max_connections_per_ip6_prefix", U32(8u32)),
+        ("network.max_connections_per_ip6_prefix_size", U32(56u32)),
+        ("network.max_connection_frequency_per_min", U32(8u32)),
+        ("network.client_whitelist_timeout_ms", U32(300_000u32)),
+        ("network.reverse_connection_receipt_time_ms", U32(5_000u32)),
+        ("network.hole_punch_receipt_time_ms", U32(5_000u32)),
+        ("network.network_key_password", OptionalText(None)),
+        (
+            "network.routing_table.bootstrap",
+            TextList(vec!["bootstrap.veilid.net".to_string()]),
+        ),
+        ("network.routing_table.limit_over_attached", U32(64u32)),
+        ("network.routing_table.limit_fully_attached", U32(32u32)),
+        ("network.routing_table.limit_attached_strong", U32(16u32)),
+        ("network.routing_table.limit_attached_good", U32(8u32)),
+        ("network.routing_table.limit_attached_weak", U32(4u32)),
+        ("network.rpc.concurrency", U32(2u32)),
+        ("network.rpc.queue_size", U32(1024u32)),
+        (
+            "network.rpc.max_timestamp_behind_ms",
+            OptionalU32(Some(10_000u32)),
+        ),
+        (
+            "network.rpc.max_timestamp_ahead_ms",
+            OptionalU32(Some(10_000u32)),
+        ),
+        ("network.rpc.timeout_ms", U32(5_000u32)),
+        ("network.rpc.max_route_hop_count", U8(4u8)),
+        ("network.rpc.default_route_hop_count", U8(1u8)),
+        ("network.dht.max_find_node_count", U32(20u32)),
+        ("network.dht.resolve_node_timeout_ms", U32(10_000u32)),
+        ("network.dht.resolve_node_count", U32(1u32)),
+        ("network.dht.resolve_node_fanout", U32(4u32)),
+        ("network.dht.get_value_timeout_ms", U32(10_000u32)),
+        ("network.dht.get_value_count", U32(3u32)),
+        ("network.dht.get_value_fanout", U32(4u32)),
+        ("network.dht.set_value_timeout_ms", U32(10_000u32)),
+        ("network.dht.set_value_count", U32(5u32)),
+        ("network.dht.set_value_fanout", U32(4u32)),
+        ("network.dht.min_peer_count", U32(20u32)),
+        ("network.dht.min_peer_refresh_time_ms", U32(60_000u32)),
+        (
+            "network.dht.validate_dial_info_receipt_time_ms",
+            U32(5_000u32),
+        ),
+        ("network.dht.local_subkey_cache_size", U32(128u32)),
+        ("network.dht.local_max_subkey_cache_memory_mb", U32(256u32)),
+        ("network.dht.remote_subkey_cache_size", U32(1024u32)),
+        ("network.dht.remote_max_records", U32(4096u32)),
+        ("network.dht.remote_max_subkey_cache_memory_mb", U32(64u32)),
+        ("network.dht.remote_max_storage_space_mb", U32(64u32)),
+        ("network.upnp", Bool(true)),
+        ("network.detect_address_changes", Bool(true)),
+        ("network.restricted_nat_retries", U32(3u32)),
+        (
+            "network.tls.certificate_path",
+            Text(path_to_string(get_veilid_certfile_path()?)?),
+        ),
+        (
+            "network.tls.private_key_path",
+            Text(path_to_string(get_veilid_keyfile_path()?)?),
+        ),
+        ("network.tls.connection_initial_timeout_ms", U32(2_000u32)),
+        ("network.application.https.enabled", Bool(false)),
+        (
+            "network.application.https.listen_address",
+            Text(String::new()),
+        ),
+        ("network.application.https.path", Text(String::from("app"))),
+        ("network.application.https.url", OptionalText(None)),
+        ("network.application.http.enabled", Bool(false)),
+        (
+            "network.application.http.listen_address",
+            Text(String::new()),
+        ),
+        ("network.application.http.path", Text(String::from("app"))),
+        ("network.application.http.url", OptionalText(None)),
+        ("network.protocol.udp.enabled", Bool(true)),
+        ("network.protocol.udp.socket_pool_size", U32(16u32)),
+        ("network.protocol.udp.listen_address", Text(String::new())),
+        ("network.protocol.udp.public_address", OptionalText(None)),
+        ("network.protocol.tcp.connect", Bool(true)),
+        ("network.protocol.tcp.listen", Bool(true)),
+        ("network.protocol.tcp.max_connections", U32(32u32)),
+        ("network.protocol.tcp.listen_address", Text(String::new())),
+        ("network.protocol.tcp.public_address", OptionalText(None)),
+        ("network.protocol.ws.connect", Bool(false)),
+        ("network.protocol.ws.listen", Bool(false)),
+        ("network.protocol.ws.max_connections", U32(16u32)),
+        ("network.protocol.ws.listen_address", Text(String::new())),
+        ("network.protocol.ws.path", Text(String::from("ws"))),
+        ("network.protocol.ws.url", OptionalText(None)),
+        ("network.protocol.wss.connect", Bool(false)),
+        ("network.protocol.wss.listen", Bool(false)),
+        ("network.protocol.wss.max_connections", U32(16u32)),
+        ("network.protocol.wss.listen_address", Text(String::new())),
+        ("network.protocol.wss.path", Text(String::from("ws"))),
+        ("network.protocol.wss.url", OptionalText(None)),
+    ];
+
+    Ok(settings
+        .into_iter()
+        .map(|(key, setting)| (key.to_string(), setting))
+        .collect())
+}
+
+/// config_callback() returns the callback veilid uses to generate its configuration at the
+/// startup. Configurable settings are taken from the given settings, which are the defaults
+/// merged with the configuration file. Everything else is fixed.
+pub fn config_callback(settings: BTreeMap<String, VeilidSetting>) -> ConfigCallback {
+    Arc::new(move |key: String| -> ConfigCallbackReturn {
+        match key.as_str() {
+            "program_name" => Ok(Box::new(String::from("bote"))),
+            "namespace" => Ok(Box::<String>::default()),
+            "capabilities.disable" => Ok(Box::<Vec<FourCC>>::default()),
+            "network.routing_table.node_id" => Ok(Box::new(TypedKeyGroup::new())),
+            "network.routing_table.node_id_secret" => Ok(Box::new(TypedSecretGroup::new())),
+            _ => match settings.get(&key) {
+                Some(setting) => Ok(setting.clone().into_config_value()),
+                None => Err(Error::NotFound { whats_missing: key }.into()),
+            },
         }
-        None => Err(Error::HomeDir),
+    })
+}
+
+/// HOME_OVERRIDE contains the app directory given with the --home flag.
+static HOME_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// USE_XDG is set if bote should split its files into the XDG base directories.
+static USE_XDG: AtomicBool = AtomicBool::new(false);
+
+/// set_home() overrides the app directory, e.g. with the --home flag. This takes precedence over
+/// the BOTE_HOME environment variable and the XDG base directories.
+pub fn set_home(home: PathBuf) {
+    if let Ok(mut home_override) = HOME_OVERRIDE.write() {
+        *home_override = Some(home);
+    }
+}
+
+/// set_use_xdg() makes bote split its files into the XDG base directories instead of keeping
+/// everything in ~/.bote. This can also be enabled by setting BOTE_USE_XDG=1.
+pub fn set_use_xdg(use_xdg: bool) {
+    USE_XDG.store(use_xdg, Ordering::Relaxed);
+}
+
+/// get_home_override() returns the app directory set with set_home() or BOTE_HOME.
+fn get_home_override() -> Option<PathBuf> {
+    if let Some(home) = HOME_OVERRIDE.read().ok().and_then(|home| home.clone()) {
+        return Some(home);
+    }
+
+    std::env::var_os("BOTE_HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// use_xdg() returns whether bote uses the XDG base directories.
+fn use_xdg() -> bool {
+    USE_XDG.load(Ordering::Relaxed)
+        || std::env::var("BOTE_USE_XDG").is_ok_and(|value| value == "1" || value == "true")
+}
+
+/// get_xdg_directory() returns the bote directory inside of an XDG base directory. If the
+/// environment variable isn't set, the default from the XDG specification is used.
+fn get_xdg_directory(variable: &str, default: &str) -> Result<PathBuf, Error> {
+    let base = match std::env::var_os(variable).filter(|base| !base.is_empty()) {
+        Some(base) => PathBuf::from(base),
+        None => home::home_dir().ok_or(Error::HomeDir)?.join(default),
+    };
+
+    Ok(base.join("bote"))
+}
+
+/// get_app_directory() returns the directory where bote keeps its data. This is the --home flag,
+/// $BOTE_HOME, $XDG_DATA_HOME/bote if XDG base directories are used, or ~/.bote.
+pub fn get_app_directory() -> Result<PathBuf, Error> {
+    if let Some(home) = get_home_override() {
+        return Ok(home);
     }
+
+    if use_xdg() {
+        return get_xdg_directory("XDG_DATA_HOME", ".local/share");
+    }
+
+    Ok(home::home_dir().ok_or(Error::HomeDir)?.join(".bote"))
+}
+
+/// get_cache_directory() returns the path to the cache directory.
+pub fn get_cache_directory() -> Result<PathBuf, Error> {
+    if get_home_override().is_none() && use_xdg() {
+        return get_xdg_directory("XDG_CACHE_HOME", ".cache");
+    }
+
+    Ok(get_app_directory()?.join(".cache"))
+}
+
+/// get_state_directory() returns the path to the directory containing state like the log file.
+pub fn get_state_directory() -> Result<PathBuf, Error> {
+    if get_home_override().is_none() && use_xdg() {
+        return get_xdg_directory("XDG_STATE_HOME", ".local/state");
+    }
+
+    get_app_directory()
+}
+
+/// get_config_directory() returns the path to the directory containing the configuration.
+pub fn get_config_directory() -> Result<PathBuf, Error> {
+    if get_home_override().is_none() && use_xdg() {
+        return get_xdg_directory("XDG_CONFIG_HOME", ".config");
+    }
+
+    get_app_directory()
+}
+
+/// path_to_string() converts a path into a string for veilid, which only accepts UTF-8 paths.
+fn path_to_string(path: PathBuf) -> Result<String, Error> {
+    path.into_os_string()
+        .into_string()
+        .map_err(|path| Error::Conversion {
+            from: path.to_string_lossy().to_string(),
+            into: "UTF-8 string".to_string(),
+        })
+}
+
+/// get_log_file_path() returns the path to the log file.
+pub fn get_log_file_path() -> Result<PathBuf, Error> {
+    Ok(get_state_directory()?.join("bote.log"))
 }
 
 /// get_veilid_table_store_path() returns the path to the veilid table store.
-pub fn get_veilid_table_store_path() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/table_store")
+pub fn get_veilid_table_store_path() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("table_store"))
 }
 
 /// get_veilid_protected_store_path() returns the path to the veilid protected store.
-pub fn get_veilid_protected_store_path() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/protected_store")
+pub fn get_veilid_protected_store_path() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("protected_store"))
 }
 
 /// get_veilid_block_store_path() returns the path to the veilid block store.
-pub fn get_veilid_block_store_path() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/block_store")
+pub fn get_veilid_block_store_path() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("block_store"))
 }
 
 /// get_veilid_certfile_path() returns the path to the veilid certificate.
-pub fn get_veilid_certfile_path() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/certfile")
+pub fn get_veilid_certfile_path() -> Result<PathBuf, Error> {
+    Ok(get_config_directory()?.join("certfile"))
 }
 
 /// get_veilid_keyfile_path() returns the path to the veilid keyfile.
-pub fn get_veilid_keyfile_path() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/keyfile")
+pub fn get_veilid_keyfile_path() -> Result<PathBuf, Error> {
+    Ok(get_config_directory()?.join("keyfile"))
 }
 
-/// get_cache_directory() returns the path to the cache directory.
-pub fn get_cache_directory() -> Result<String, Error> {
-    Ok(get_app_directory()? + "/.cache")
+/// get_database_directory() returns the path to the directory containing bote's databases.
+pub fn get_database_directory() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("db"))
+}
+
+/// get_installed_database_path() returns the path to the database of installed packages.
+pub fn get_installed_database_path() -> Result<PathBuf, Error> {
+    Ok(get_database_directory()?.join("installed.json"))
+}
+
+/// get_library_registry_path() returns the path to the registry of imported libraries.
+pub fn get_library_registry_path() -> Result<PathBuf, Error> {
+    Ok(get_database_directory()?.join("libraries.json"))
+}
+
+/// get_library_cache_directory() returns the path to the directory where the build scripts of
+/// imported libraries are cached.
+pub fn get_library_cache_directory() -> Result<PathBuf, Error> {
+    Ok(get_cache_directory()?.join("libraries"))
+}
+
+/// get_config_file_path() returns the path to the bote configuration file.
+pub fn get_config_file_path() -> Result<PathBuf, Error> {
+    Ok(get_config_directory()?.join("config.json"))
+}
+
+/// get_store_directory() returns the path to the store which contains every installed version
+/// of every package.
+pub fn get_store_directory() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("store"))
+}
+
+/// get_profiles_directory() returns the path to the directory containing all profile
+/// generations.
+pub fn get_profiles_directory() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("profiles"))
+}
+
+/// get_profile_path() returns the path to the symlink that points to the active profile
+/// generation. The bin directory of the profile (e.g. ~/.bote/profile/bin) should be in the
+/// PATH.
+pub fn get_profile_path() -> Result<PathBuf, Error> {
+    Ok(get_app_directory()?.join("profile"))
 }
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::error::Error;
+use crate::package::PackageReference;
+use crate::version::{Version, VersionRequirement};
+
+/// An entry of the installed package database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstalledPackage {
+    /// The name of the package.
+    pub name: String,
+    /// The version returned by the version() function of the build script.
+    pub version: String,
+    /// The DHT key of the library the package was installed from. This is None for packages that
+    /// were installed from a local build script.
+    pub library: Option<String>,
+    /// Whether the package was installed explicitly or as a dependency of another package.
+    pub explicit: bool,
+    /// The time of the installation in seconds since the unix epoch.
+    pub installed_at: u64,
+    /// The bote dependencies of the installed version.
+    pub bote_dependencies: Vec<PackageReference>,
+}
+
+impl InstalledPackage {
+    /// new() creates a new database entry for a package that was installed just now.
+    pub fn new(
+        name: &str,
+        version: &str,
+        library: Option<String>,
+        explicit: bool,
+        bote_dependencies: Vec<PackageReference>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            library,
+            explicit,
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            bote_dependencies,
+        }
+    }
+}
+
+/// The database of all packages installed by bote.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InstalledDatabase {
+    packages: BTreeMap<String, InstalledPackage>,
+    /// Packages that are never upgraded.
+    #[serde(default)]
+    held: BTreeSet<String>,
+    /// Version requirements that packages must always satisfy.
+    #[serde(default)]
+    pins: BTreeMap<String, VersionRequirement>,
+}
+
+impl InstalledDatabase {
+    /// load() loads the installed package database. An empty database is returned if bote
+    /// didn't install anything yet.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = config::get_installed_database_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// save() writes the database to disk. The database is written to a temporary file first
+    /// which then replaces the old database, so a failed write never corrupts it.
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = config::get_installed_database_path()?;
+        fs::create_dir_all(config::get_database_directory()?)?;
+
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(&temporary_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary_path, &path)?;
+
+        Ok(())
+    }
+
+    /// get() returns the entry of an installed package.
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.get(name)
+    }
+
+    /// get_or_not_found() returns the entry of an installed package or an error if the package
+    /// isn't installed.
+    pub fn get_or_not_found(&self, name: &str) -> Result<&InstalledPackage, Error> {
+        self.get(name).ok_or(Error::PackageNotFound {
+            package: name.to_string(),
+            location: "the installed packages".to_string(),
+        })
+    }
+
+    /// insert() adds a package to the database or replaces its old entry.
+    pub fn insert(&mut self, package: InstalledPackage) {
+        self.packages.insert(package.name.clone(), package);
+    }
+
+    /// remove() removes a package from the database.
+    pub fn remove(&mut self, name: &str) -> Option<InstalledPackage> {
+        self.packages.remove(name)
+    }
+
+    /// set_packages() replaces all installed packages, e.g. after switching to another
+    /// generation. Holds and pins are kept.
+    pub fn set_packages(&mut self, packages: Vec<InstalledPackage>) {
+        self.packages = packages
+            .into_iter()
+            .map(|package| (package.name.clone(), package))
+            .collect();
+    }
+
+    /// packages() returns all installed packages ordered by their name.
+    pub fn packages(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.packages.values()
+    }
+
+    /// versions() returns the installed version of every package.
+    pub fn versions(&self) -> BTreeMap<String, Version> {
+        self.packages()
+            .map(|package| (package.name.clone(), Version::parse(&package.version)))
+            .collect()
+    }
+
+    /// hold() prevents a package from being upgraded. It returns false if the package was
+    /// already held.
+    pub fn hold(&mut self, name: &str) -> bool {
+        self.held.insert(name.to_string())
+    }
+
+    /// unhold() allows a held package to be upgraded again. It returns false if the package
+    /// wasn't held.
+    pub fn unhold(&mut self, name: &str) -> bool {
+        self.held.remove(name)
+    }
+
+    /// is_held() returns whether a package is held.
+    pub fn is_held(&self, name: &str) -> bool {
+        self.held.contains(name)
+    }
+
+    /// pin() restricts the versions of a package to a version requirement. Pins may also be set
+    /// for packages that aren't installed yet.
+    pub fn pin(&mut self, name: &str, requirement: &VersionRequirement) {
+        self.pins.insert(name.to_string(), requirement.clone());
+    }
+
+    /// unpin() removes the pin of a package and returns it.
+    pub fn unpin(&mut self, name: &str) -> Option<VersionRequirement> {
+        self.pins.remove(name)
+    }
+
+    /// get_pin() returns the version requirement a package is pinned to.
+    pub fn get_pin(&self, name: &str) -> Option<VersionRequirement> {
+        self.pins.get(name).cloned()
+    }
+}
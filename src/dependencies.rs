@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::database::{InstalledDatabase, InstalledPackage};
+use crate::error::Error;
+use crate::package::PackageReference;
+use crate::version::{Version, VersionRequirement};
+
+/// sort_by_dependencies() orders packages so that every package comes after its dependencies.
+/// The map contains the names of the packages and the names of their dependencies. Dependencies
+/// that aren't part of the map are ignored, since they don't have to be processed. An error is
+/// returned if the packages depend on each other in a cycle.
+pub fn sort_by_dependencies(
+    packages: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<String>, Error> {
+    let mut sorted = Vec::new();
+    let mut done = BTreeSet::new();
+    let mut remaining: BTreeSet<&String> = packages.keys().collect();
+
+    while !remaining.is_empty() {
+        let ready: Vec<&String> = remaining
+            .iter()
+            .filter(|package| {
+                packages[package.as_str()].iter().all(|dependency| {
+                    !packages.contains_key(dependency) || done.contains(dependency)
+                })
+            })
+            .copied()
+            .collect();
+
+        if ready.is_empty() {
+            return Err(Error::DependencyCycle {
+                packages: remaining.into_iter().cloned().collect(),
+            });
+        }
+
+        for package in ready {
+            remaining.remove(package);
+            done.insert(package.clone());
+            sorted.push(package.clone());
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// check_pins() makes sure that installing a package in the given version doesn't violate any
+/// pins. Pins are hard constraints: a package must satisfy its own pin and the version
+/// requirements of its dependencies must have a version in common with the pins of the
+/// dependencies, whether the dependencies are installed or not.
+pub fn check_pins(
+    package: &str,
+    version: &Version,
+    dependencies: &[PackageReference],
+    database: &InstalledDatabase,
+) -> Result<(), Error> {
+    if let Some(pin) = database.get_pin(package) {
+        if !pin.matches(version) {
+            return Err(Error::Pinned {
+                package: package.to_string(),
+                version: version.to_string(),
+                pin: pin.to_string(),
+            });
+        }
+    }
+
+    for dependency in dependencies {
+        let (Some(pin), Some(requirement)) =
+            (database.get_pin(&dependency.name), &dependency.requirement)
+        else {
+            continue;
+        };
+
+        if !VersionRequirement::parse(requirement)?.intersects(&pin) {
+            return Err(Error::PinConflict {
+                package: package.to_string(),
+                dependency: dependency.name.clone(),
+                requirement: requirement.clone(),
+                pin: pin.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// check_requirement() makes sure that the version a dependency has once the current operation is
+/// finished satisfies the version requirement of the package that depends on it.
+pub fn check_requirement(
+    package: &str,
+    dependency: &PackageReference,
+    version: &Version,
+) -> Result<(), Error> {
+    let Some(requirement) = &dependency.requirement else {
+        return Ok(());
+    };
+
+    if !VersionRequirement::parse(requirement)?.matches(version) {
+        return Err(Error::UnsatisfiedDependency {
+            package: package.to_string(),
+            dependency: dependency.name.clone(),
+            requirement: requirement.clone(),
+            version: version.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// check_dependents() makes sure that the installed packages that depend on a package still
+/// accept the version the package has once the current operation is finished.
+pub fn check_dependents<'a>(
+    package: &str,
+    version: &Version,
+    dependents: impl IntoIterator<Item = &'a InstalledPackage>,
+) -> Result<(), Error> {
+    for dependent in dependents {
+        for dependency in &dependent.bote_dependencies {
+            if dependency.name == package {
+                check_requirement(&dependent.name, dependency, version)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// check_conflicts() makes sure that none of the packages a package conflicts with is installed.
+/// A conflict only matches an installed package from the same library, and only if the installed
+/// version satisfies the version requirement of the conflict.
+pub fn check_conflicts(
+    package: &str,
+    conflicts: &[PackageReference],
+    database: &InstalledDatabase,
+) -> Result<(), Error> {
+    for conflict in conflicts {
+        let Some(installed) = database.get(&conflict.name) else {
+            continue;
+        };
+        if installed.library.as_ref() != Some(&conflict.library) {
+            continue;
+        }
+        if let Some(requirement) = &conflict.requirement {
+            if !VersionRequirement::parse(requirement)?.matches(&Version::parse(&installed.version))
+            {
+                continue;
+            }
+        }
+
+        return Err(Error::DependencyConflict {
+            package: package.to_string(),
+            conflict: format!("{} {}", installed.name, installed.version),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(name: &str, requirement: &str) -> PackageReference {
+        PackageReference {
+            library: "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI".to_string(),
+            name: name.to_string(),
+            requirement: Some(requirement.to_string()),
+        }
+    }
+
+    fn pinned(name: &str, pin: &str) -> InstalledDatabase {
+        let mut database = InstalledDatabase::default();
+        database.pin(name, &VersionRequirement::parse(pin).unwrap());
+        database
+    }
+
+    fn dependent(name: &str, dependencies: Vec<PackageReference>) -> InstalledPackage {
+        InstalledPackage::new(name, "1.0.0", None, true, dependencies)
+    }
+
+    #[test]
+    fn check_dependents_reports_rejected_versions() {
+        let dependents = [
+            dependent("bote", vec![dependency("rhai", "^1.16")]),
+            dependent("bar", Vec::new()),
+        ];
+
+        let result = check_dependents("rhai", &Version::parse("2.0.0"), &dependents);
+
+        assert!(matches!(
+            result,
+            Err(Error::UnsatisfiedDependency { package, dependency, .. })
+                if package == "bote" && dependency == "rhai"
+        ));
+    }
+
+    #[test]
+    fn check_dependents_accepts_matching_versions() {
+        let dependents = [dependent("bote", vec![dependency("rhai", "^1.16")])];
+
+        check_dependents("rhai", &Version::parse("1.17.0"), &dependents).unwrap();
+        check_dependents("veilid", &Version::parse("0.1.0"), &dependents).unwrap();
+    }
+
+    #[test]
+    fn check_pins_reports_conflicting_requirement() {
+        let database = pinned("rhai", ">=1.16, <1.17");
+        let dependencies = [dependency("rhai", ">=1.17")];
+
+        let result = check_pins("bote", &Version::parse("0.2.0"), &dependencies, &database);
+
+        assert!(matches!(
+            result,
+            Err(Error::PinConflict { dependency, .. }) if dependency == "rhai"
+        ));
+    }
+
+    #[test]
+    fn check_pins_accepts_overlapping_requirement() {
+        let database = pinned("rhai", "~1.16");
+        let dependencies = [dependency("rhai", ">1.16.2, <2")];
+
+        check_pins("bote", &Version::parse("0.2.0"), &dependencies, &database).unwrap();
+    }
+
+    #[test]
+    fn check_pins_checks_dependencies_that_are_not_installed() {
+        let database = pinned("rhai", "=1.15.0");
+        assert!(database.get("rhai").is_none());
+
+        let conflicting = [dependency("rhai", "^1.16")];
+        assert!(matches!(
+            check_pins("bote", &Version::parse("0.2.0"), &conflicting, &database),
+            Err(Error::PinConflict { .. })
+        ));
+
+        let matching = [dependency("rhai", ">=1.14")];
+        check_pins("bote", &Version::parse("0.2.0"), &matching, &database).unwrap();
+    }
+
+    #[test]
+    fn check_pins_checks_the_pin_of_the_package() {
+        let database = pinned("bote", "<0.2");
+
+        assert!(matches!(
+            check_pins("bote", &Version::parse("0.2.0"), &[], &database),
+            Err(Error::Pinned { .. })
+        ));
+    }
+
+    #[test]
+    fn check_pins_compares_exact_versions() {
+        let database = pinned("firmware", "2023-01-05");
+
+        let conflicting = [dependency("firmware", "2023-02-01")];
+        assert!(matches!(
+            check_pins("bote", &Version::parse("0.2.0"), &conflicting, &database),
+            Err(Error::PinConflict { .. })
+        ));
+
+        let matching = [dependency("firmware", "2023-01-05")];
+        check_pins("bote", &Version::parse("0.2.0"), &matching, &database).unwrap();
+    }
+}
@@ -1,10 +1,14 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
+use std::io;
+use std::path::Path;
 
+use rhai::{Dynamic, EvalAltResult, Position};
+use serde::Serialize;
 use thiserror::Error;
 use veilid_core::VeilidAPIError;
 
 /// The bote error type.
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum Error {
     #[error("failed to access or find the home directory")]
     HomeDir,
@@ -12,6 +16,371 @@ pub enum Error {
     Conversion { from: String, into: String },
     #[error("{whats_missing} does not exist")]
     NotFound { whats_missing: String },
+    #[error(
+        "package {package} exists in several libraries {libraries:?}, use <library>/{package}"
+    )]
+    AmbiguousPackage {
+        package: String,
+        libraries: Vec<String>,
+    },
+    #[error("dependency cycle between the packages {packages:?}")]
+    DependencyCycle { packages: Vec<String> },
+    #[error("veilid did not attach to the network within {seconds} seconds")]
+    AttachmentTimeout { seconds: u64 },
+    #[error("interrupted")]
+    Interrupted,
+    #[error("unknown setting {key}")]
+    UnknownSetting { key: String },
+    #[error("invalid value for setting {key}, expected {expected}")]
+    InvalidSetting { key: String, expected: String },
+    #[error("{package} is required by {dependents:?}")]
+    RequiredBy {
+        package: String,
+        dependents: Vec<String>,
+    },
+    #[error("generation {number} is the active generation")]
+    ActiveGeneration { number: u64 },
+    #[error("{package} {version} does not satisfy its pin {pin}")]
+    Pinned {
+        package: String,
+        version: String,
+        pin: String,
+    },
+    #[error("{package} requires {dependency} {requirement}, but {dependency} is pinned to {pin}")]
+    PinConflict {
+        package: String,
+        dependency: String,
+        requirement: String,
+        pin: String,
+    },
+    #[error("invalid version requirement {requirement:?}, expected a semver requirement like \">=1.2, <2\" or an exact version")]
+    InvalidRequirement { requirement: String },
+    #[error("{package} requires {dependency} {requirement}, but the version of {dependency} is {version}")]
+    UnsatisfiedDependency {
+        package: String,
+        dependency: String,
+        requirement: String,
+        version: String,
+    },
+    #[error("library record uses schema version {version}, update bote to read it")]
+    UnsupportedLibrarySchema { version: u32 },
+    #[error("DHT value of {size} bytes is larger than the maximum of {max} bytes")]
+    ValueTooLarge { size: usize, max: usize },
+    #[error("library {library} was changed by someone else, sync it and try again")]
+    StaleLibrary { library: String },
+    #[error("failed to access the protected store: {message}")]
+    ProtectedStore { message: String },
+    #[error("the alias {alias} is already used by library {library}")]
+    AliasTaken { alias: String, library: String },
+    #[error("invalid library alias {alias}, it must not be empty or contain a /")]
+    InvalidAlias { alias: String },
+    #[error("invalid package name {name:?}, it must not be empty, start with a . or contain /, \\ or ..")]
+    InvalidPackageName { name: String },
+    #[error("{package} {version} is already published in library {library}")]
+    VersionExists {
+        package: String,
+        version: String,
+        library: String,
+    },
+    #[error("library {library} has no free subkeys for build scripts left")]
+    LibraryFull { library: String },
+    #[error("your identity {identity} is not a writer of library {library}")]
+    NotWriter { library: String, identity: String },
+    #[error("invalid build script: {reason}")]
+    InvalidBuildScript { reason: String },
+    #[error("library {library} is not trusted, import it again with --trust or pass --insecure")]
+    UntrustedLibrary { library: String },
+    #[error("the signature of {package} is invalid: {reason} (pass --insecure to skip the verification)")]
+    SignatureInvalid { package: String, reason: String },
+    #[error("blob of {size} bytes is larger than the maximum of {max} bytes")]
+    BlobTooLarge { size: u64, max: u64 },
+    #[error("checksum mismatch of {what}")]
+    ChecksumMismatch { what: String },
+    #[error("the manifest of blob {blob} is inconsistent")]
+    InvalidBlob { blob: String },
+    #[error("subkey {subkey} of blob record {record} holds a newer value than the one written")]
+    BlobConflict { record: String, subkey: u32 },
+    #[error("artifact entry {entry} would be unpacked outside of the install prefix")]
+    UnsafeArtifactEntry { entry: String },
+    #[error("the veilid network is unavailable: {reason}")]
+    NetworkUnavailable { reason: String },
+    #[error("package {package} was not found in {location}")]
+    PackageNotFound { package: String, location: String },
+    #[error("{package} conflicts with the installed package {conflict}")]
+    DependencyConflict { package: String, conflict: String },
+    #[error(transparent)]
+    Script(Box<ScriptError>),
+    #[error("command \"{command}\" failed with {}", describe_exit_code(.code))]
+    CommandFailed { command: String, code: Option<i32> },
+    #[error("{missing} does not exist, restore it or remove {existing} to generate a new pair")]
+    IncompleteCertificate { missing: String, existing: String },
+    #[error("permission denied for {path}")]
+    PermissionDenied { path: String },
+}
+
+/// describe_exit_code() describes the exit code of a command, which is None if the command was
+/// killed by a signal.
+fn describe_exit_code(code: &Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exit code {}", code),
+        None => "a signal".to_string(),
+    }
+}
+
+impl Error {
+    /// kind() returns the class of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NetworkUnavailable { .. } | Error::AttachmentTimeout { .. } => {
+                ErrorKind::NetworkUnavailable
+            }
+            Error::PackageNotFound { .. } => ErrorKind::PackageNotFound,
+            Error::DependencyConflict { .. }
+            | Error::DependencyCycle { .. }
+            | Error::RequiredBy { .. }
+            | Error::Pinned { .. }
+            | Error::PinConflict { .. }
+            | Error::UnsatisfiedDependency { .. } => ErrorKind::DependencyConflict,
+            Error::SignatureInvalid { .. } => ErrorKind::SignatureInvalid,
+            Error::Script(error) => error
+                .cause
+                .as_ref()
+                .map_or(ErrorKind::Script, |cause| cause.kind()),
+            Error::InvalidBuildScript { .. } => ErrorKind::Script,
+            Error::CommandFailed { .. } => ErrorKind::CommandFailed,
+            Error::ChecksumMismatch { .. } => ErrorKind::ChecksumMismatch,
+            Error::PermissionDenied { .. } | Error::NotWriter { .. } => ErrorKind::PermissionDenied,
+            Error::Interrupted => ErrorKind::Interrupted,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// An error of a build script. It is rendered with the location in the script, a snippet of the
+/// source and the call stack, e.g.
+///
+/// ```text
+/// the build script foo.bote.rhai failed during download(): Function not found: fetch (&str)
+///   --> foo.bote.rhai:12:5
+///    |
+/// 12 |     fetch("https://example.com/foo.tar.gz");
+///    |     ^
+/// call stack, outermost first:
+///   download() at foo.bote.rhai:3:5
+///   fetch_sources() at foo.bote.rhai:12:5
+/// ```
+#[derive(Serialize, Debug, Clone)]
+pub struct ScriptError {
+    /// The file name of the build script.
+    pub script: String,
+    /// The stage that was running: COMPILE_STAGE or the function bote called, like "download".
+    pub stage: String,
+    /// The message of rhai without the position.
+    pub message: String,
+    /// The line of the error, if rhai knows it.
+    pub line: Option<usize>,
+    /// The column of the error, if rhai knows it.
+    pub column: Option<usize>,
+    /// The source line the error points into.
+    pub source_line: Option<String>,
+    /// The functions of the build script that were running, outermost first. It is empty unless
+    /// the error happened in a nested function call.
+    pub call_stack: Vec<ScriptFrame>,
+    /// The bote error a native function raised, like a failed command.
+    #[serde(skip)]
+    pub cause: Option<Box<Error>>,
+}
+
+/// A function of a build script that was running when an error happened.
+#[derive(Serialize, Debug, Clone)]
+pub struct ScriptFrame {
+    /// The name of the function.
+    pub function: String,
+    /// The line the function was at, if rhai knows it.
+    pub line: Option<usize>,
+    /// The column the function was at, if rhai knows it.
+    pub column: Option<usize>,
+}
+
+impl ScriptError {
+    /// COMPILE_STAGE is the stage of errors that happen while a build script is compiled.
+    pub const COMPILE_STAGE: &'static str = "compile";
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.stage == Self::COMPILE_STAGE {
+            write!(
+                f,
+                "the build script {} failed to compile: {}",
+                self.script, self.message
+            )?;
+        } else {
+            write!(
+                f,
+                "the build script {} failed during {}(): {}",
+                self.script, self.stage, self.message
+            )?;
+        }
+
+        if let Some(line) = self.line {
+            let gutter = " ".repeat(line.to_string().len());
+            write!(
+                f,
+                "\n{}--> {}",
+                gutter,
+                location(&self.script, Some(line), self.column)
+            )?;
+
+            if let Some(source_line) = &self.source_line {
+                // tabs are kept, so the caret lines up with the source no matter how tabs are shown
+                let indent: String = source_line
+                    .chars()
+                    .take(self.column.unwrap_or(1).saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                write!(f, "\n{} |", gutter)?;
+                write!(f, "\n{} | {}", line, source_line)?;
+                write!(f, "\n{} | {}^", gutter, indent)?;
+            }
+        }
+
+        if !self.call_stack.is_empty() {
+            write!(f, "\ncall stack, outermost first:")?;
+            for frame in &self.call_stack {
+                write!(
+                    f,
+                    "\n  {}() at {}",
+                    frame.function,
+                    location(&self.script, frame.line, frame.column)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|cause| cause as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// location() formats a location in a build script like file:line:column, leaving out what is
+/// unknown.
+fn location(script: &str, line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!("{}:{}:{}", script, line, column),
+        (Some(line), None) => format!("{}:{}", script, line),
+        _ => format!("{}, unknown position", script),
+    }
+}
+
+/// The classes of errors bote distinguishes. Every class has its own exit code, so scripts that
+/// wrap bote can react to failures:
+///
+/// | Exit code | Class               |
+/// |-----------|---------------------|
+/// | 0         | success             |
+/// | 1         | other               |
+/// | 2         | invalid arguments   |
+/// | 3         | network unavailable |
+/// | 4         | package not found   |
+/// | 5         | dependency conflict |
+/// | 6         | signature invalid   |
+/// | 7         | script error        |
+/// | 8         | command failed      |
+/// | 9         | checksum mismatch   |
+/// | 10        | permission denied   |
+/// | 130       | interrupted         |
+///
+/// Invalid arguments are reported by clap before bote runs, so they have no class.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Any error that doesn't belong to another class.
+    Other,
+    /// Veilid couldn't attach to the network or a network operation failed.
+    NetworkUnavailable,
+    /// A package doesn't exist in the libraries or isn't installed.
+    PackageNotFound,
+    /// Packages conflict with each other, depend on each other in a cycle or violate a pin.
+    DependencyConflict,
+    /// The signature of a build script is missing or invalid.
+    SignatureInvalid,
+    /// A build script failed to compile or raised an error while running.
+    Script,
+    /// A command a build script executed failed.
+    CommandFailed,
+    /// Downloaded data doesn't match its checksum.
+    ChecksumMismatch,
+    /// bote isn't allowed to access a file or to write to a library.
+    PermissionDenied,
+    /// bote was interrupted with Ctrl-C.
+    Interrupted,
+}
+
+impl ErrorKind {
+    /// of() returns the class of an error. The first error of the chain that belongs to a class
+    /// determines it, so context added to an error doesn't change its class.
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .map(|cause| {
+                if let Some(error) = cause.downcast_ref::<Error>() {
+                    return error.kind();
+                }
+                if let Some(error) = cause.downcast_ref::<io::Error>() {
+                    if error.kind() == io::ErrorKind::PermissionDenied {
+                        return ErrorKind::PermissionDenied;
+                    }
+                }
+                if let Some(
+                    VeilidAPIError::NoConnection { .. }
+                    | VeilidAPIError::Timeout
+                    | VeilidAPIError::TryAgain,
+                ) = cause.downcast_ref::<VeilidAPIError>()
+                {
+                    return ErrorKind::NetworkUnavailable;
+                }
+
+                ErrorKind::Other
+            })
+            .find(|kind| *kind != ErrorKind::Other)
+            .unwrap_or(ErrorKind::Other)
+    }
+
+    /// exit_code() returns the process exit code of the class.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::NetworkUnavailable => 3,
+            ErrorKind::PackageNotFound => 4,
+            ErrorKind::DependencyConflict => 5,
+            ErrorKind::SignatureInvalid => 6,
+            ErrorKind::Script => 7,
+            ErrorKind::CommandFailed => 8,
+            ErrorKind::ChecksumMismatch => 9,
+            ErrorKind::PermissionDenied => 10,
+            ErrorKind::Interrupted => 130,
+        }
+    }
+}
+
+/// with_path() converts an io error caused by missing permissions into Error::PermissionDenied,
+/// so the error names the path bote tried to access. Other io errors are kept.
+pub fn with_path(error: io::Error, path: &Path) -> anyhow::Error {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => Error::PermissionDenied {
+            path: path.display().to_string(),
+        }
+        .into(),
+        _ => error.into(),
+    }
 }
 
 impl From<Error> for VeilidAPIError {
@@ -21,3 +390,13 @@ impl From<Error> for VeilidAPIError {
         }
     }
 }
+
+impl From<Error> for Box<EvalAltResult> {
+    fn from(value: Error) -> Self {
+        // the error is kept as it is, so bote can recover it when the build script fails
+        Box::new(EvalAltResult::ErrorRuntime(
+            Dynamic::from(value),
+            Position::NONE,
+        ))
+    }
+}
@@ -1,8 +1,40 @@
+/// artifact contains the packing of prebuilt artifacts of package versions
+pub mod artifact;
+/// blob contains the store for large files that are split into chunks on the DHT
+pub mod blob;
 /// commands contains all subcommands of bote
 pub mod commands;
 /// config contains the functionality to configure bote
 pub mod config;
+/// database contains the database of installed packages
+pub mod database;
+/// dependencies contains the functionality to order packages by their dependencies
+pub mod dependencies;
 /// error contains the error functionality for bote
 pub mod error;
+/// library contains the functionality to access the packages of libraries
+pub mod library;
 /// logging contains all functions that handle the loging initialisation
 pub mod logging;
+/// node contains the veilid node that is used by subcommands that need the network
+pub mod node;
+/// output contains the output formats of bote and its JSON output
+pub mod output;
+/// package contains types that describe packages
+pub mod package;
+/// profile contains the functionality to manage the generations of the profile
+pub mod profile;
+/// prompt contains functions to interact with the user
+pub mod prompt;
+/// registry contains the registry of imported libraries
+pub mod registry;
+/// secrets contains the functionality to keep keypairs in veilid's protected store
+pub mod secrets;
+/// settings contains the settings that are read from the configuration file
+pub mod settings;
+/// status contains the status of the veilid node and the handling of veilid updates
+pub mod status;
+/// store contains the functionality to manage the store paths of package versions
+pub mod store;
+/// version contains the functionality to compare package versions
+pub mod version;
@@ -0,0 +1,209 @@
+pub mod record;
+pub mod sync;
+
+use std::fs;
+use std::path::PathBuf;
+
+use veilid_core::CryptoSystemVersion;
+
+use record::{BinaryArtifact, PublishedVersion};
+use sync::LibraryCache;
+
+use crate::config;
+use crate::error::Error;
+use crate::registry::Registry;
+
+/// BUILD_SCRIPT_EXTENSION is appended to the package name to get the file name of its build
+/// script.
+pub const BUILD_SCRIPT_EXTENSION: &str = ".bote.rhai";
+
+/// build_script_name() returns the file name of the build script of a package.
+pub fn build_script_name(package: &str) -> String {
+    package.to_string() + BUILD_SCRIPT_EXTENSION
+}
+
+/// validate_package_name() returns an error if a package name can't be used as a file name, so
+/// names from a library can't point outside of its cache directory.
+pub fn validate_package_name(name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains('/')
+        || name.contains('\\')
+        || name.contains("..")
+    {
+        return Err(Error::InvalidPackageName {
+            name: name.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// get_build_script_path() returns the path of the cached build script of a package.
+fn get_build_script_path(library: &str, package: &str) -> Result<PathBuf, Error> {
+    validate_package_name(package)?;
+
+    Ok(config::get_library_cache_directory()?
+        .join(library)
+        .join(build_script_name(package)))
+}
+
+/// fetch_build_script() returns the current build script of a package from its library.
+pub fn fetch_build_script(library: &str, package: &str) -> Result<String, anyhow::Error> {
+    let path = get_build_script_path(library, package)?;
+
+    if !path.exists() {
+        return Err(Error::PackageNotFound {
+            package: package.to_string(),
+            location: format!("library {}", library),
+        }
+        .into());
+    }
+
+    Ok(fs::read_to_string(path)?)
+}
+
+/// verify_build_script() verifies the signature of a cached build script against the writers of
+/// its library. The signature and the writers are taken from the synced index of the library.
+pub fn verify_build_script(
+    crypto: &CryptoSystemVersion,
+    library: &str,
+    package: &str,
+    buildscript: &str,
+) -> Result<(), anyhow::Error> {
+    let (cache, version, published) = read_published_version(library, package)?;
+
+    record::verify(
+        crypto,
+        library,
+        &cache.header,
+        package,
+        &version,
+        &published,
+        buildscript,
+    )?;
+
+    Ok(())
+}
+
+/// find_artifact() returns the prebuilt artifact for a target of the version of a package whose
+/// build script is cached. The artifact is covered by the signature of the build script.
+pub fn find_artifact(
+    library: &str,
+    package: &str,
+    target: &str,
+) -> Result<Option<BinaryArtifact>, anyhow::Error> {
+    let (_, _, published) = read_published_version(library, package)?;
+
+    Ok(published.artifact(target).cloned())
+}
+
+/// read_published_version() returns the synced cache of a library together with the version of
+/// a package whose build script is cached and its entry in the index.
+fn read_published_version(
+    library: &str,
+    package: &str,
+) -> Result<(LibraryCache, String, PublishedVersion), anyhow::Error> {
+    let cache = sync::read_cache(library)?.ok_or_else(|| Error::NotFound {
+        whats_missing: format!("synced index of library {}", library),
+    })?;
+    let version = cache
+        .scripts
+        .get(package)
+        .ok_or_else(|| Error::PackageNotFound {
+            package: package.to_string(),
+            location: format!("the index of library {}", library),
+        })?
+        .version
+        .clone();
+    let published = cache
+        .index
+        .get(package, &version)
+        .ok_or_else(|| Error::NotFound {
+            whats_missing: format!(
+                "{} {} in the index of library {}",
+                package, version, library
+            ),
+        })?
+        .clone();
+
+    Ok((cache, version, published))
+}
+
+/// find_package() returns the DHT key of the imported library that contains a package. An error
+/// is returned if the package is part of several libraries, since bote can't decide which one to
+/// use.
+pub fn find_package(package: &str) -> Result<String, anyhow::Error> {
+    let registry = Registry::load()?;
+    let mut libraries = Vec::new();
+
+    for library in registry.libraries() {
+        if get_build_script_path(&library.key, package)?.exists() {
+            libraries.push(library);
+        }
+    }
+
+    match libraries.as_slice() {
+        [] => Err(Error::PackageNotFound {
+            package: package.to_string(),
+            location: "the synced libraries".to_string(),
+        }
+        .into()),
+        [library] => Ok(library.key.clone()),
+        _ => Err(Error::AmbiguousPackage {
+            package: package.to_string(),
+            libraries: libraries
+                .iter()
+                .map(|library| library.alias.clone())
+                .collect(),
+        }
+        .into()),
+    }
+}
+
+/// find_library() returns the DHT key of the library of a package. The library is given by its
+/// alias or DHT key, or found among the imported libraries if it is None.
+pub fn find_library(library: Option<&str>, package: &str) -> Result<String, anyhow::Error> {
+    match library {
+        Some(library) => Ok(Registry::load()?.resolve(library)?.key.clone()),
+        None => find_package(package),
+    }
+}
+
+/// remove_cache() deletes the cached build scripts of a library.
+pub fn remove_cache(library: &str) -> Result<(), anyhow::Error> {
+    let path = config::get_library_cache_directory()?.join(library);
+    if path.exists() {
+        fs::remove_dir_all(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_package_names() {
+        for name in ["ripgrep", "rust-analyzer", "python3.12", "a.b", "_"] {
+            assert!(validate_package_name(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_package_names() {
+        for name in [
+            "", ".", "..", ".hidden", "a/b", "/etc", "a\\b", "a..b", "../x",
+        ] {
+            assert!(
+                matches!(
+                    validate_package_name(name),
+                    Err(Error::InvalidPackageName { .. })
+                ),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,866 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use veilid_core::{
+    CryptoSystem, CryptoSystemVersion, DHTSchema, DHTSchemaSMPLMember, KeyPair, PublicKey,
+    RoutingContext, Signature, TypedKey, ValueData, ValueSeqNum, ValueSubkey, VeilidAPIError,
+};
+
+use crate::error::Error;
+use crate::package::PackageReference;
+use crate::version::Version;
+
+/// LIBRARY_SCHEMA_VERSION is the version of the library record format that is written. Records
+/// with a higher version were written by a newer bote and are rejected.
+pub const LIBRARY_SCHEMA_VERSION: u32 = 1;
+
+/// HEADER_SUBKEY is the subkey of a library record that contains the LibraryHeader.
+pub const HEADER_SUBKEY: ValueSubkey = 0;
+
+/// INDEX_SUBKEY is the subkey of a library record that contains the LibraryIndex of the owner.
+pub const INDEX_SUBKEY: ValueSubkey = 1;
+
+/// FIRST_SCRIPT_SUBKEY is the first subkey of a library record that can hold a build script.
+pub const FIRST_SCRIPT_SUBKEY: ValueSubkey = 2;
+
+/// OWNER_SUBKEY_COUNT is the number of subkeys the owner of a library record can write.
+pub const OWNER_SUBKEY_COUNT: u16 = 256;
+
+/// MEMBER_SUBKEY_COUNT is the number of subkeys every additional writer of a library record can
+/// write. The subkeys of writers follow the subkeys of the owner. The first subkey of every
+/// writer contains its own LibraryIndex, since writers can't write the subkeys of the owner.
+pub const MEMBER_SUBKEY_COUNT: u16 = 64;
+
+/// The header of a library that describes the library itself.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LibraryHeader {
+    /// The name of the library.
+    pub name: String,
+    /// A short description of the library.
+    pub description: String,
+    /// The public key of the owner of the library record.
+    pub owner: String,
+    /// The public keys of the additional writers of the library record.
+    #[serde(default)]
+    pub writers: Vec<String>,
+}
+
+/// The location of a build script on the DHT.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLocation {
+    /// The DHT key of the record that contains the build script.
+    pub record: String,
+    /// The subkey of the record that contains the build script.
+    pub subkey: ValueSubkey,
+}
+
+/// The metadata of a published package version, as returned by its build script.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageMetadata {
+    /// The packages of other libraries the package depends on.
+    #[serde(default)]
+    pub bote_dependencies: Vec<PackageReference>,
+    /// The packages the package conflicts with.
+    #[serde(default)]
+    pub conflicts: Vec<PackageReference>,
+    /// Regexes of programs that have to be installed to build the package.
+    #[serde(default)]
+    pub installed_program_dependencies: Vec<String>,
+    // the following fields are optional in build scripts and skipped if empty, so signatures of
+    // versions published without them stay valid
+    /// A short description of the package.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Keywords that help to find the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    /// The license of the package, e.g. an SPDX expression.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+/// A published version of a package.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PublishedVersion {
+    /// The location of the build script.
+    pub script: ScriptLocation,
+    /// The metadata of the version.
+    #[serde(flatten)]
+    pub metadata: PackageMetadata,
+    /// The time of publishing in seconds since the unix epoch.
+    pub published_at: u64,
+    /// The signature of the build script, its metadata and its artifacts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ScriptSignature>,
+    /// The prebuilt artifacts of the version.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<BinaryArtifact>,
+}
+
+impl PublishedVersion {
+    /// artifact() returns the prebuilt artifact of the version for a target triple.
+    pub fn artifact(&self, target: &str) -> Option<&BinaryArtifact> {
+        self.artifacts
+            .iter()
+            .find(|artifact| artifact.target == target)
+    }
+}
+
+/// A prebuilt artifact of a package version. It is the packed install prefix of the build script
+/// for a target triple, stored as a blob.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BinaryArtifact {
+    /// The target triple the artifact was built for, e.g. x86_64-unknown-linux-gnu.
+    pub target: String,
+    /// The key of the blob that contains the packed artifact.
+    pub blob: String,
+    /// The hash of the packed artifact.
+    pub hash: String,
+    /// The size of the packed artifact in bytes.
+    pub size: u64,
+}
+
+/// The signature of a published build script and its metadata, made by a writer of the library.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScriptSignature {
+    /// The public key of the writer who signed the build script.
+    pub signer: String,
+    /// The signature.
+    pub signature: String,
+}
+
+/// The data that is signed when a build script is published. It contains the library, so a
+/// signed build script can't be copied into another library.
+#[derive(Serialize)]
+struct SignedData<'a> {
+    context: &'a str,
+    library: &'a str,
+    package: &'a str,
+    version: &'a str,
+    metadata: &'a PackageMetadata,
+    buildscript: &'a str,
+    // skipped if empty, so signatures of versions without artifacts stay valid
+    #[serde(skip_serializing_if = "no_artifacts")]
+    artifacts: &'a [BinaryArtifact],
+}
+
+/// no_artifacts() returns whether the signed data doesn't contain artifacts.
+fn no_artifacts(artifacts: &&[BinaryArtifact]) -> bool {
+    artifacts.is_empty()
+}
+
+/// A package of a library index.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexedPackage {
+    /// All published versions of the package by version.
+    pub versions: BTreeMap<String, PublishedVersion>,
+}
+
+impl IndexedPackage {
+    /// latest() returns the latest published version of the package.
+    pub fn latest(&self) -> Option<(&str, &PublishedVersion)> {
+        self.versions
+            .iter()
+            .max_by_key(|(version, _)| Version::parse(version))
+            .map(|(version, published)| (version.as_str(), published))
+    }
+}
+
+/// The index of all packages of a library.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryIndex {
+    /// All packages of the library by name.
+    pub packages: BTreeMap<String, IndexedPackage>,
+}
+
+impl LibraryIndex {
+    /// get() returns a published version of a package.
+    pub fn get(&self, package: &str, version: &str) -> Option<&PublishedVersion> {
+        self.packages.get(package)?.versions.get(version)
+    }
+
+    /// insert() adds a published version of a package or replaces it.
+    pub fn insert(&mut self, package: &str, version: &str, published: PublishedVersion) {
+        self.packages
+            .entry(package.to_string())
+            .or_default()
+            .versions
+            .insert(version.to_string(), published);
+    }
+
+    /// merge() merges the indexes of all writers of a library into one index. If several writers
+    /// published the same version of a package, the version of the earlier writer is used.
+    pub fn merge(indexes: &[LibraryIndex]) -> LibraryIndex {
+        let mut merged = LibraryIndex::default();
+
+        for index in indexes {
+            for (package, indexed) in &index.packages {
+                let versions = &mut merged.packages.entry(package.clone()).or_default().versions;
+                for (version, published) in &indexed.versions {
+                    versions
+                        .entry(version.clone())
+                        .or_insert_with(|| published.clone());
+                }
+            }
+        }
+
+        merged
+    }
+
+    /// script_locations() returns the locations of all build scripts in the index.
+    fn script_locations(&self) -> impl Iterator<Item = &ScriptLocation> {
+        self.packages
+            .values()
+            .flat_map(|indexed| indexed.versions.values())
+            .map(|published| &published.script)
+    }
+}
+
+/// A library as it is stored in its DHT record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+    /// The DHT key of the library record.
+    pub key: String,
+    /// The header of the library.
+    pub header: LibraryHeader,
+    /// The package indexes of the writers of the library. The index of the owner comes first,
+    /// followed by the indexes of the additional writers in the order of the header.
+    pub indexes: Vec<LibraryIndex>,
+}
+
+impl Library {
+    /// index() returns the merged package index of all writers.
+    pub fn index(&self) -> LibraryIndex {
+        LibraryIndex::merge(&self.indexes)
+    }
+
+    /// writer_number() returns the number of a writer of the library, 0 for the owner. None is
+    /// returned if the key can't write to the library.
+    pub fn writer_number(&self, key: &PublicKey) -> Option<usize> {
+        let key = key.to_string();
+
+        if self.header.owner == key {
+            return Some(0);
+        }
+
+        self.header
+            .writers
+            .iter()
+            .position(|writer| *writer == key)
+            .map(|position| position + 1)
+    }
+}
+
+/// A build script that is published to a library.
+#[derive(Debug, Clone)]
+pub struct Publication<'a> {
+    /// The name of the package.
+    pub package: &'a str,
+    /// The version of the package.
+    pub version: &'a str,
+    /// The build script.
+    pub buildscript: &'a str,
+    /// The metadata returned by the build script.
+    pub metadata: PackageMetadata,
+    /// The prebuilt artifacts of the version.
+    pub artifacts: Vec<BinaryArtifact>,
+}
+
+/// A keypair that can write to a library together with its writer number.
+#[derive(Debug, Clone, Copy)]
+pub struct Writer {
+    /// The number of the writer, 0 for the owner.
+    pub number: usize,
+    /// The keypair of the writer.
+    pub keypair: KeyPair,
+}
+
+/// Every subkey value is wrapped into an envelope that carries the schema version it was
+/// written with.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    value: T,
+}
+
+/// The schema version of a subkey value, used to check the version before decoding the rest.
+#[derive(Deserialize)]
+struct SchemaVersion {
+    schema_version: u32,
+}
+
+/// library_schema() returns the DHT schema of library records. Every writer besides the owner
+/// becomes a member of the record with its own subkeys.
+pub fn library_schema(writers: &[PublicKey]) -> DHTSchema {
+    if writers.is_empty() {
+        return DHTSchema::dflt(OWNER_SUBKEY_COUNT);
+    }
+
+    DHTSchema::smpl(
+        OWNER_SUBKEY_COUNT,
+        writers
+            .iter()
+            .map(|writer| DHTSchemaSMPLMember {
+                m_key: *writer,
+                m_cnt: MEMBER_SUBKEY_COUNT,
+            })
+            .collect(),
+    )
+}
+
+/// SIGNATURE_CONTEXT is part of the signed data, so signatures of build scripts can't be
+/// mistaken for signatures of anything else.
+const SIGNATURE_CONTEXT: &str = "bote build script signature v1";
+
+/// signed_data() returns the data that is signed for a published build script.
+fn signed_data(
+    library: &str,
+    package: &str,
+    version: &str,
+    metadata: &PackageMetadata,
+    buildscript: &str,
+    artifacts: &[BinaryArtifact],
+) -> Result<Vec<u8>, serde_json::Error> {
+    serde_json::to_vec(&SignedData {
+        context: SIGNATURE_CONTEXT,
+        library,
+        package,
+        version,
+        metadata,
+        buildscript,
+        artifacts,
+    })
+}
+
+/// sign() signs a build script and its metadata with the keypair of a writer.
+fn sign(
+    crypto: &CryptoSystemVersion,
+    library: &str,
+    writer: &KeyPair,
+    publication: &Publication,
+) -> Result<ScriptSignature, anyhow::Error> {
+    let data = signed_data(
+        library,
+        publication.package,
+        publication.version,
+        &publication.metadata,
+        publication.buildscript,
+        &publication.artifacts,
+    )?;
+    let signature = crypto.sign(&writer.key, &writer.secret, &data)?;
+
+    Ok(ScriptSignature {
+        signer: writer.key.to_string(),
+        signature: signature.to_string(),
+    })
+}
+
+/// verify() verifies the signature of a published build script. The signer has to be the owner
+/// or one of the writers in the header of the library.
+pub fn verify(
+    crypto: &CryptoSystemVersion,
+    library: &str,
+    header: &LibraryHeader,
+    package: &str,
+    version: &str,
+    published: &PublishedVersion,
+    buildscript: &str,
+) -> Result<(), Error> {
+    let invalid = |reason: &str| Error::SignatureInvalid {
+        package: package.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let signature = published
+        .signature
+        .as_ref()
+        .ok_or_else(|| invalid("the build script is not signed"))?;
+
+    if header.owner != signature.signer && !header.writers.contains(&signature.signer) {
+        return Err(invalid("the signer is not a writer of the library"));
+    }
+
+    let signer = PublicKey::from_str(&signature.signer)
+        .map_err(|_| invalid("the public key of the signer is malformed"))?;
+    let signature_bytes = Signature::from_str(&signature.signature)
+        .map_err(|_| invalid("the signature is malformed"))?;
+    let data = signed_data(
+        library,
+        package,
+        version,
+        &published.metadata,
+        buildscript,
+        &published.artifacts,
+    )
+    .map_err(|e| invalid(&e.to_string()))?;
+
+    crypto
+        .verify(&signer, &data, &signature_bytes)
+        .map_err(|_| invalid("the signature does not match the build script"))
+}
+
+/// index_subkey() returns the subkey that contains the index of a writer.
+pub fn index_subkey(writer: usize) -> ValueSubkey {
+    if writer == 0 {
+        return INDEX_SUBKEY;
+    }
+
+    OWNER_SUBKEY_COUNT as ValueSubkey
+        + (writer as ValueSubkey - 1) * MEMBER_SUBKEY_COUNT as ValueSubkey
+}
+
+/// script_subkeys() returns the subkeys a writer can store build scripts in.
+pub fn script_subkeys(writer: usize) -> Range<ValueSubkey> {
+    if writer == 0 {
+        return FIRST_SCRIPT_SUBKEY..OWNER_SUBKEY_COUNT as ValueSubkey;
+    }
+
+    let index_subkey = index_subkey(writer);
+    index_subkey + 1..index_subkey + MEMBER_SUBKEY_COUNT as ValueSubkey
+}
+
+/// encode() encodes a value of a library record into the data of a subkey.
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, anyhow::Error> {
+    let data = serde_json::to_vec(&Envelope {
+        schema_version: LIBRARY_SCHEMA_VERSION,
+        value,
+    })?;
+
+    if data.len() > ValueData::MAX_LEN {
+        return Err(Error::ValueTooLarge {
+            size: data.len(),
+            max: ValueData::MAX_LEN,
+        }
+        .into());
+    }
+
+    Ok(data)
+}
+
+/// decode() decodes the data of a subkey into a value of a library record.
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, anyhow::Error> {
+    let SchemaVersion { schema_version } = serde_json::from_slice(data)?;
+    if schema_version > LIBRARY_SCHEMA_VERSION {
+        return Err(Error::UnsupportedLibrarySchema {
+            version: schema_version,
+        }
+        .into());
+    }
+
+    let envelope: Envelope<T> = serde_json::from_slice(data)?;
+    Ok(envelope.value)
+}
+
+/// parse_key() parses the DHT key of a record.
+pub fn parse_key(key: &str) -> Result<TypedKey, Error> {
+    TypedKey::from_str(key).map_err(|_| Error::Conversion {
+        from: key.to_string(),
+        into: "DHT key".to_string(),
+    })
+}
+
+/// create() creates a new, empty library record that the given writers can write to as well.
+/// veilid generates a new owner keypair for the record. The library and the owner keypair are
+/// returned, since the owner keypair is needed to update the library later on.
+pub async fn create(
+    routing_context: &RoutingContext,
+    name: &str,
+    description: &str,
+    writers: &[PublicKey],
+) -> Result<(Library, KeyPair), anyhow::Error> {
+    let descriptor = routing_context
+        .create_dht_record(library_schema(writers), None)
+        .await?;
+    let key = *descriptor.key();
+
+    let owner_secret = descriptor
+        .owner_secret()
+        .ok_or_else(|| VeilidAPIError::generic("created record has no owner secret"))?;
+    let owner = KeyPair::new(*descriptor.owner(), *owner_secret);
+
+    let library = Library {
+        key: key.to_string(),
+        header: LibraryHeader {
+            name: name.to_string(),
+            description: description.to_string(),
+            owner: descriptor.owner().to_string(),
+            writers: writers.iter().map(|writer| writer.to_string()).collect(),
+        },
+        indexes: vec![LibraryIndex::default()],
+    };
+
+    let result = async {
+        write_subkey(routing_context, key, HEADER_SUBKEY, &library.header).await?;
+        write_subkey(routing_context, key, INDEX_SUBKEY, &library.indexes[0]).await
+    }
+    .await;
+    routing_context.close_dht_record(key).await?;
+    result?;
+
+    Ok((library, owner))
+}
+
+/// read() reads a library from the DHT. The values are always refreshed from the network.
+/// Writers that never published anything have an empty index.
+pub async fn read(routing_context: &RoutingContext, key: &str) -> Result<Library, anyhow::Error> {
+    let key = parse_key(key)?;
+    routing_context.open_dht_record(key, None).await?;
+
+    let result = async {
+        let header: LibraryHeader = read_subkey(routing_context, key, HEADER_SUBKEY).await?;
+
+        let mut indexes = Vec::new();
+        for writer in 0..=header.writers.len() {
+            let index = match get_optional_value(routing_context, key, index_subkey(writer)).await?
+            {
+                Some(value) => decode(value.data())?,
+                None => LibraryIndex::default(),
+            };
+            indexes.push(index);
+        }
+
+        Ok(Library {
+            key: key.to_string(),
+            header,
+            indexes,
+        })
+    }
+    .await;
+    routing_context.close_dht_record(key).await?;
+
+    result
+}
+
+/// publish() signs a build script, uploads it into a free subkey of a writer and adds it to the
+/// index of the writer afterwards. The index is updated with a single write, so readers either
+/// see the old index or the new one which points to the uploaded script. An existing version in
+/// the index of the writer is only replaced if force is set.
+pub async fn publish(
+    routing_context: &RoutingContext,
+    crypto: &CryptoSystemVersion,
+    library: &str,
+    writer: &Writer,
+    publication: Publication<'_>,
+    force: bool,
+) -> Result<ScriptLocation, anyhow::Error> {
+    let signature = sign(crypto, library, &writer.keypair, &publication)?;
+    let Publication {
+        package,
+        version,
+        buildscript,
+        metadata,
+        artifacts,
+    } = publication;
+    let key = parse_key(library)?;
+    routing_context
+        .open_dht_record(key, Some(writer.keypair))
+        .await?;
+
+    let result = async {
+        let subkey = index_subkey(writer.number);
+        let mut index = match get_optional_value(routing_context, key, subkey).await? {
+            Some(value) => decode(value.data())?,
+            None => LibraryIndex::default(),
+        };
+
+        if index.get(package, version).is_some() && !force {
+            return Err(Error::VersionExists {
+                package: package.to_string(),
+                version: version.to_string(),
+                library: library.to_string(),
+            }
+            .into());
+        }
+
+        // a replaced version gets a new subkey too, so syncs notice the new build script
+        let script_subkey = script_subkeys(writer.number)
+            .find(|candidate| {
+                !index
+                    .script_locations()
+                    .any(|location| location.record == library && location.subkey == *candidate)
+            })
+            .ok_or_else(|| Error::LibraryFull {
+                library: library.to_string(),
+            })?;
+
+        let data = buildscript.as_bytes().to_vec();
+        if data.len() > ValueData::MAX_LEN {
+            return Err(Error::ValueTooLarge {
+                size: data.len(),
+                max: ValueData::MAX_LEN,
+            }
+            .into());
+        }
+        if routing_context
+            .set_dht_value(key, script_subkey, data)
+            .await?
+            .is_some()
+        {
+            return Err(Error::StaleLibrary {
+                library: library.to_string(),
+            }
+            .into());
+        }
+
+        let location = ScriptLocation {
+            record: library.to_string(),
+            subkey: script_subkey,
+        };
+        index.insert(
+            package,
+            version,
+            PublishedVersion {
+                script: location.clone(),
+                metadata,
+                published_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default(),
+                signature: Some(signature),
+                artifacts,
+            },
+        );
+        write_subkey(routing_context, key, subkey, &index).await?;
+
+        Ok(location)
+    }
+    .await;
+    routing_context.close_dht_record(key).await?;
+
+    result
+}
+
+/// read_script() reads a build script from the DHT. The script is returned together with the
+/// sequence number of its subkey.
+pub async fn read_script(
+    routing_context: &RoutingContext,
+    location: &ScriptLocation,
+) -> Result<(String, ValueSeqNum), anyhow::Error> {
+    let key = parse_key(&location.record)?;
+    routing_context.open_dht_record(key, None).await?;
+
+    let result = get_value(routing_context, key, location.subkey).await;
+    routing_context.close_dht_record(key).await?;
+    let value = result?;
+
+    Ok((String::from_utf8(value.data().to_vec())?, value.seq()))
+}
+
+/// get_value() reads a subkey of an opened record. The value is always refreshed from the
+/// network.
+pub async fn get_value(
+    routing_context: &RoutingContext,
+    key: TypedKey,
+    subkey: ValueSubkey,
+) -> Result<ValueData, anyhow::Error> {
+    Ok(get_optional_value(routing_context, key, subkey)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            whats_missing: format!("subkey {} of record {}", subkey, key),
+        })?)
+}
+
+/// get_optional_value() reads a subkey of an opened record that may not be set yet. The value is
+/// always refreshed from the network.
+pub async fn get_optional_value(
+    routing_context: &RoutingContext,
+    key: TypedKey,
+    subkey: ValueSubkey,
+) -> Result<Option<ValueData>, anyhow::Error> {
+    Ok(routing_context.get_dht_value(key, subkey, true).await?)
+}
+
+/// read_subkey() reads and decodes a subkey of an opened library record.
+async fn read_subkey<T: DeserializeOwned>(
+    routing_context: &RoutingContext,
+    key: TypedKey,
+    subkey: ValueSubkey,
+) -> Result<T, anyhow::Error> {
+    decode(get_value(routing_context, key, subkey).await?.data())
+}
+
+/// write_subkey() encodes and writes a subkey of an opened library record. If the network
+/// already has a newer value, the library was changed concurrently and an error is returned.
+async fn write_subkey<T: Serialize>(
+    routing_context: &RoutingContext,
+    key: TypedKey,
+    subkey: ValueSubkey,
+    value: &T,
+) -> Result<(), anyhow::Error> {
+    if routing_context
+        .set_dht_value(key, subkey, encode(value)?)
+        .await?
+        .is_some()
+    {
+        return Err(Error::StaleLibrary {
+            library: key.to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> LibraryHeader {
+        LibraryHeader {
+            name: "core".to_string(),
+            description: "The core packages".to_string(),
+            owner: "6_bPrlkSj1Go6gLYFDvRhaUAT1shEFsXENlkc1bShO0".to_string(),
+            writers: vec!["uHVfSnlZVYRtXNWQ5jXNnc5bAMtQjM_IJr2gFUvcoN0".to_string()],
+        }
+    }
+
+    fn published(subkey: ValueSubkey) -> PublishedVersion {
+        PublishedVersion {
+            script: ScriptLocation {
+                record: "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI".to_string(),
+                subkey,
+            },
+            metadata: PackageMetadata {
+                bote_dependencies: vec![PackageReference {
+                    library: "VLD0:gH1XKm6RBzOl8FqUNxAQ_pTNyg0ZQ1ob4dYQWo5fnhI".to_string(),
+                    name: "rhai".to_string(),
+                    requirement: Some(">=1.16".to_string()),
+                }],
+                conflicts: Vec::new(),
+                installed_program_dependencies: vec!["cargo".to_string()],
+                description: "A package manager on veilid".to_string(),
+                keywords: vec!["veilid".to_string()],
+                license: Some("GPL-3.0".to_string()),
+            },
+            published_at: 1_700_000_000,
+            signature: Some(ScriptSignature {
+                signer: "6_bPrlkSj1Go6gLYFDvRhaUAT1shEFsXENlkc1bShO0".to_string(),
+                signature: "signature".to_string(),
+            }),
+            artifacts: vec![BinaryArtifact {
+                target: "x86_64-unknown-linux-gnu".to_string(),
+                blob: "VLD0:Bx4aGMa7h2SQw0sRq5ZQ5aDlJPLVs3Ks7JKsOXbnUq8".to_string(),
+                hash: "VLD0:9qM8zmyq8ZL7vVFgnAQVcK4vBvSvPZa7d0sxOgqXDTs".to_string(),
+                size: 4096,
+            }],
+        }
+    }
+
+    fn index() -> LibraryIndex {
+        let mut index = LibraryIndex::default();
+        index.insert("bote", "1.0.0", published(2));
+        index.insert("bote", "1.1.0", published(3));
+        index
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let data = encode(&header()).unwrap();
+        assert_eq!(decode::<LibraryHeader>(&data).unwrap(), header());
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let data = encode(&index()).unwrap();
+        assert_eq!(decode::<LibraryIndex>(&data).unwrap(), index());
+    }
+
+    #[test]
+    fn empty_index_round_trip() {
+        let data = encode(&LibraryIndex::default()).unwrap();
+        assert_eq!(
+            decode::<LibraryIndex>(&data).unwrap(),
+            LibraryIndex::default()
+        );
+    }
+
+    #[test]
+    fn latest_version_is_compared_as_version() {
+        let mut index = index();
+        index.insert("bote", "1.10.0", published(4));
+
+        let (version, published) = index.packages["bote"].latest().unwrap();
+        assert_eq!(version, "1.10.0");
+        assert_eq!(published.script.subkey, 4);
+    }
+
+    #[test]
+    fn artifacts_are_found_by_target() {
+        let published = published(2);
+
+        assert_eq!(
+            published.artifact("x86_64-unknown-linux-gnu").unwrap().size,
+            4096
+        );
+        assert!(published.artifact("aarch64-apple-darwin").is_none());
+    }
+
+    #[test]
+    fn signed_data_without_artifacts_is_unchanged() {
+        let metadata = PackageMetadata::default();
+        let data = signed_data("library", "bote", "1.0.0", &metadata, "script", &[]).unwrap();
+
+        assert!(!String::from_utf8(data).unwrap().contains("artifacts"));
+    }
+
+    #[test]
+    fn merge_prefers_earlier_writers() {
+        let mut member = LibraryIndex::default();
+        member.insert("bote", "1.1.0", published(257));
+        member.insert("bote", "1.2.0", published(258));
+
+        let merged = LibraryIndex::merge(&[index(), member]);
+        assert_eq!(merged.get("bote", "1.1.0").unwrap().script.subkey, 3);
+        assert_eq!(merged.get("bote", "1.2.0").unwrap().script.subkey, 258);
+        assert_eq!(merged.packages["bote"].versions.len(), 3);
+    }
+
+    #[test]
+    fn writer_subkeys_do_not_overlap() {
+        assert_eq!(index_subkey(0), INDEX_SUBKEY);
+        assert_eq!(script_subkeys(0), FIRST_SCRIPT_SUBKEY..256);
+        assert_eq!(index_subkey(1), 256);
+        assert_eq!(script_subkeys(1), 257..320);
+        assert_eq!(index_subkey(2), 320);
+        assert_eq!(script_subkeys(2), 321..384);
+    }
+
+    #[test]
+    fn encoded_values_carry_the_schema_version() {
+        let data = encode(&header()).unwrap();
+        let SchemaVersion { schema_version } = serde_json::from_slice(&data).unwrap();
+        assert_eq!(schema_version, LIBRARY_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn newer_schema_versions_are_rejected() {
+        let data = serde_json::to_vec(&Envelope {
+            schema_version: LIBRARY_SCHEMA_VERSION + 1,
+            value: header(),
+        })
+        .unwrap();
+
+        let error = decode::<LibraryHeader>(&data).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::UnsupportedLibrarySchema { .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_values_are_rejected() {
+        let mut header = header();
+        header.description = "x".repeat(ValueData::MAX_LEN);
+
+        let error = encode(&header).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::ValueTooLarge { .. })
+        ));
+    }
+}
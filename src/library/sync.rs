@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use veilid_core::{RoutingContext, ValueSeqNum};
+
+use super::record::{self, LibraryHeader, LibraryIndex, ScriptLocation, HEADER_SUBKEY};
+use super::{get_build_script_path, validate_package_name};
+use crate::config;
+use crate::error::Error;
+
+/// A build script in the local cache of a library.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedScript {
+    /// The version of the package the build script belongs to.
+    pub version: String,
+    /// The location of the build script on the DHT.
+    pub location: ScriptLocation,
+    /// The sequence number of the subkey the build script was read from.
+    pub sequence_number: ValueSeqNum,
+}
+
+/// The local cache of a library. Build scripts of the latest version of every package are
+/// stored next to it as <package>.bote.rhai.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LibraryCache {
+    /// The header of the library.
+    pub header: LibraryHeader,
+    /// The merged package index of all writers of the library.
+    pub index: LibraryIndex,
+    /// The sequence number of the header subkey.
+    pub header_sequence_number: ValueSeqNum,
+    /// The sequence numbers of the index subkeys of all writers. Writers that never published
+    /// anything have no index subkey yet.
+    pub index_sequence_numbers: Vec<Option<ValueSeqNum>>,
+    /// The cached build scripts by package name.
+    pub scripts: BTreeMap<String, CachedScript>,
+}
+
+/// The changes a sync brought into the local cache.
+#[derive(Serialize, Debug, Default)]
+pub struct SyncSummary {
+    /// Whether the header or the index changed.
+    pub changed: bool,
+    /// The number of build scripts that were fetched.
+    pub fetched_scripts: usize,
+    /// The number of build scripts that were removed, since their package was removed.
+    pub removed_scripts: usize,
+}
+
+/// get_cache_path() returns the path of the cached state of a library.
+fn get_cache_path(library: &str) -> Result<PathBuf, Error> {
+    Ok(config::get_library_cache_directory()?
+        .join(library)
+        .join("library.json"))
+}
+
+/// read_cache() returns the cached state of a library, or None if it was never synced.
+pub fn read_cache(library: &str) -> Result<Option<LibraryCache>, anyhow::Error> {
+    let path = get_cache_path(library)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// write_cache() writes the cached state of a library. It is written to a temporary file first,
+/// so an interrupted sync never corrupts the cache.
+fn write_cache(library: &str, cache: &LibraryCache) -> Result<(), anyhow::Error> {
+    let path = get_cache_path(library)?;
+
+    let temporary_path = path.with_extension("json.tmp");
+    fs::write(&temporary_path, serde_json::to_string_pretty(cache)?)?;
+    fs::rename(&temporary_path, &path)?;
+
+    Ok(())
+}
+
+/// sync() pulls the header and the indexes of all writers of a library into the local cache.
+/// The sync is incremental: nothing else is fetched if the sequence numbers of the header and
+/// the indexes didn't change, and only build scripts whose version or location changed are
+/// fetched again.
+pub async fn sync(
+    routing_context: &RoutingContext,
+    library: &str,
+) -> Result<SyncSummary, anyhow::Error> {
+    let key = record::parse_key(library)?;
+    let cached = read_cache(library)?;
+    let mut summary = SyncSummary::default();
+
+    routing_context.open_dht_record(key, None).await?;
+    let result = async {
+        let header_value = record::get_value(routing_context, key, HEADER_SUBKEY).await?;
+        let header: LibraryHeader = record::decode(header_value.data())?;
+
+        let mut index_values = Vec::new();
+        for writer in 0..=header.writers.len() {
+            index_values.push(
+                record::get_optional_value(routing_context, key, record::index_subkey(writer))
+                    .await?,
+            );
+        }
+
+        Ok::<_, anyhow::Error>((header_value.seq(), header, index_values))
+    }
+    .await;
+    routing_context.close_dht_record(key).await?;
+    let (header_sequence_number, header, index_values) = result?;
+
+    let index_sequence_numbers: Vec<Option<ValueSeqNum>> = index_values
+        .iter()
+        .map(|value| value.as_ref().map(|value| value.seq()))
+        .collect();
+
+    if let Some(cached) = &cached {
+        if cached.header_sequence_number == header_sequence_number
+            && cached.index_sequence_numbers == index_sequence_numbers
+        {
+            debug!("Library {} is unchanged", library);
+            return Ok(summary);
+        }
+    }
+    summary.changed = true;
+
+    let indexes = index_values
+        .iter()
+        .map(|value| match value {
+            Some(value) => record::decode(value.data()),
+            None => Ok(LibraryIndex::default()),
+        })
+        .collect::<Result<Vec<LibraryIndex>, _>>()?;
+
+    let mut index = LibraryIndex::merge(&indexes);
+    index
+        .packages
+        .retain(|package, _| match validate_package_name(package) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Skipping a package of library {}: {}", library, e);
+                false
+            }
+        });
+
+    let mut cache = LibraryCache {
+        header,
+        index,
+        header_sequence_number,
+        index_sequence_numbers,
+        scripts: BTreeMap::new(),
+    };
+    let mut cached_scripts = cached.map(|cached| cached.scripts).unwrap_or_default();
+
+    fs::create_dir_all(config::get_library_cache_directory()?.join(library))?;
+
+    for (package, indexed) in &cache.index.packages {
+        let Some((version, published)) = indexed.latest() else {
+            continue;
+        };
+        let location = &published.script;
+        let path = get_build_script_path(library, package)?;
+
+        if let Some(script) = cached_scripts.remove(package) {
+            if script.version == version && &script.location == location && path.exists() {
+                cache.scripts.insert(package.clone(), script);
+                continue;
+            }
+        }
+
+        debug!("Fetching the build script of {} {}", package, version);
+        let (buildscript, sequence_number) = record::read_script(routing_context, location).await?;
+        fs::write(&path, buildscript)?;
+        summary.fetched_scripts += 1;
+
+        cache.scripts.insert(
+            package.clone(),
+            CachedScript {
+                version: version.to_string(),
+                location: location.clone(),
+                sequence_number,
+            },
+        );
+    }
+
+    // the remaining scripts belong to packages that were removed from the library
+    for package in cached_scripts.keys() {
+        let path = get_build_script_path(library, package)?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        summary.removed_scripts += 1;
+    }
+
+    write_cache(library, &cache)?;
+
+    Ok(summary)
+}
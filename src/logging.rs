@@ -1,4 +1,5 @@
-use crate::config::get_app_directory;
+use crate::config::{get_log_file_path, get_state_directory};
+use crate::output;
 use fern::colors::Color;
 use fern::colors::ColoredLevelConfig;
 use std::time::SystemTime;
@@ -12,7 +13,8 @@ fn setup_logger_color_scheme() -> ColoredLevelConfig {
         .trace(Color::BrightBlack)
 }
 
-/// setup_stdout_logging() registers a fern dispatch logger that logs to stdout.
+/// setup_stdout_logging() registers a fern dispatch logger that logs to stdout, or to stderr if the
+/// output format is JSON.
 fn setup_stdout_logging(verbosity: u8) -> Result<fern::Dispatch, anyhow::Error> {
     let colors = setup_logger_color_scheme();
 
@@ -34,14 +36,21 @@ fn setup_stdout_logging(verbosity: u8) -> Result<fern::Dispatch, anyhow::Error>
                 message
             ))
         })
-        .level(filter)
-        .chain(std::io::stdout());
+        .level(filter);
 
-    Ok(dispatch)
+    // logs must not end up in the JSON document on stdout
+    if output::is_json() {
+        return Ok(dispatch.chain(std::io::stderr()));
+    }
+
+    Ok(dispatch.chain(std::io::stdout()))
 }
 
-/// setup_file_logging() registers a fern dispatch logger that logs to ~/.bote/bote.log.
+/// setup_file_logging() registers a fern dispatch logger that logs to ~/.bote/bote.log. The state
+/// directory is created if it doesn't exist yet, e.g. before bote was initialized.
 fn setup_file_logging() -> Result<fern::Dispatch, anyhow::Error> {
+    std::fs::create_dir_all(get_state_directory()?)?;
+
     let dispatch = fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
@@ -53,7 +62,7 @@ fn setup_file_logging() -> Result<fern::Dispatch, anyhow::Error> {
             ))
         })
         .level(log::LevelFilter::Trace)
-        .chain(fern::log_file(get_app_directory()? + "/bote.log")?);
+        .chain(fern::log_file(get_log_file_path()?)?);
     Ok(dispatch)
 }
 
@@ -1,11 +1,20 @@
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use bote::commands;
-use bote::config::{config_callback, get_app_directory};
+use bote::commands::blob::BlobCommands;
+use bote::commands::config::ConfigCommands;
+use bote::commands::generations::GenerationsCommands;
+use bote::commands::library::LibraryCommands;
+use bote::commands::list::ListFilters;
+use bote::commands::search::SearchOptions;
+use bote::config::{self, get_log_file_path};
+use bote::error::{Error, ErrorKind};
 use bote::logging;
-use clap::{Parser, Subcommand};
-use log::info;
-use veilid_core::VeilidUpdate;
+use bote::node::VeilidNode;
+use bote::output::{self, OutputFormat};
+use bote::settings;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(
@@ -15,6 +24,34 @@ use veilid_core::VeilidUpdate;
 struct Cli {
     #[arg(long, help = "Delete the old log file")]
     clear_log_file: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Use this directory instead of ~/.bote (overrides BOTE_HOME)"
+    )]
+    home: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        help = "Split data, cache, state and configuration into the XDG base directories"
+    )]
+    xdg: bool,
+    #[arg(
+        long = "set",
+        global = true,
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        help = "Override a setting for this invocation (takes precedence over BOTE_* variables)"
+    )]
+    settings: Vec<(String, String)>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Print the result as a JSON document on stdout and everything else on stderr"
+    )]
+    output: OutputFormat,
     #[arg(short, help = "Increase the verbosity of the output (maximum is -vvv)", action = clap::ArgAction::Count)]
     verbosity: u8,
 
@@ -24,66 +61,295 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    #[command(about = "Store and fetch large files, like package sources, on the veilid DHT")]
+    Blob {
+        #[command(subcommand)]
+        command: BlobCommands,
+    },
+    #[command(about = "Inspect and edit the settings of bote")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    #[command(about = "Manage the generations of the profile")]
+    Generations {
+        #[command(subcommand)]
+        command: GenerationsCommands,
+    },
+    #[command(about = "Prevent an installed package from being upgraded")]
+    Hold {
+        #[arg(help = "The name of the package")]
+        package: String,
+    },
+    #[command(about = "Show the details of a package")]
+    Info {
+        #[arg(help = "The name of the package, optionally as <library>/<package>")]
+        package: String,
+        #[arg(
+            long,
+            help = "The alias or DHT key of the library that contains the package"
+        )]
+        library: Option<String>,
+        #[arg(long, help = "Print the build script for review")]
+        script: bool,
+    },
     #[command(about = "Initialize bote")]
     Init,
     #[command(about = "Install a package")]
-    Install,
+    Install {
+        #[arg(help = "The name of the package, optionally as <library>/<package>")]
+        package: String,
+        #[arg(
+            long,
+            help = "The alias or DHT key of the library that contains the package"
+        )]
+        library: Option<String>,
+        #[arg(long, help = "Install the package from a local build script")]
+        script: Option<PathBuf>,
+        #[arg(long, help = "Skip the signature verification of the build script")]
+        insecure: bool,
+        #[arg(
+            long,
+            help = "Build the package even if a prebuilt artifact is available"
+        )]
+        build_from_source: bool,
+    },
     #[command(about = "Import or create a library")]
-    Library,
+    Library {
+        #[command(subcommand)]
+        command: LibraryCommands,
+    },
+    #[command(about = "List the installed packages")]
+    List {
+        #[command(flatten)]
+        filters: ListFilters,
+    },
+    #[command(about = "Restrict the versions of a package to a version requirement")]
+    Pin {
+        #[arg(help = "The name of the package")]
+        package: String,
+        #[arg(help = "The version requirement, e.g. \">=1.2, <2\" or \"=1.4.0\"")]
+        requirement: String,
+    },
     #[command(about = "Show a random silly pride flag :3")]
     Pride,
     #[command(about = "Publish a package to a library")]
-    Publish,
+    Publish {
+        #[arg(help = "The build script of the package")]
+        script: PathBuf,
+        #[arg(long, help = "The alias or DHT key of the library")]
+        library: String,
+        #[arg(
+            long,
+            help = "The name of the package (defaults to the name of the script)"
+        )]
+        name: Option<String>,
+        #[arg(long, help = "Replace the version if it was already published")]
+        force: bool,
+        #[arg(
+            long,
+            help = "Build the package and publish the result as a prebuilt artifact for this machine"
+        )]
+        prebuilt: bool,
+    },
+    #[command(about = "Roll a package back to a previously installed version")]
+    Rollback {
+        #[arg(help = "The name of the package")]
+        package: String,
+        #[arg(
+            long,
+            help = "The version to roll back to (defaults to the previous version)"
+        )]
+        to: Option<String>,
+    },
     #[command(about = "Search your imported libraries for a package")]
-    Search,
+    Search {
+        #[command(flatten)]
+        options: SearchOptions,
+    },
+    #[command(about = "Show the status of the veilid node")]
+    Status,
+    #[command(about = "Allow a held package to be upgraded again")]
+    Unhold {
+        #[arg(help = "The name of the package")]
+        package: String,
+    },
     #[command(about = "Uninstall a package")]
-    Uninstall,
+    Uninstall {
+        #[arg(help = "The name of the package")]
+        package: String,
+    },
+    #[command(about = "Remove the version pin of a package")]
+    Unpin {
+        #[arg(help = "The name of the package")]
+        package: String,
+    },
     #[command(about = "Upgrade installed packages")]
-    Upgrade,
+    Upgrade {
+        #[arg(help = "Only upgrade the given packages")]
+        packages: Vec<String>,
+        #[arg(long, help = "Show the upgrade plan without upgrading anything")]
+        dry_run: bool,
+        #[arg(short, long, help = "Don't ask for confirmation before upgrading")]
+        yes: bool,
+        #[arg(long, help = "Skip the signature verification of the build scripts")]
+        insecure: bool,
+        #[arg(
+            long,
+            help = "Build the packages even if prebuilt artifacts are available"
+        )]
+        build_from_source: bool,
+    },
 }
 
-fn run_subcommand(command: Commands) -> Result<(), anyhow::Error> {
+/// parse_key_value() parses the KEY=VALUE argument of the --set flag.
+fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+    argument
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or(format!("expected KEY=VALUE, got {}", argument))
+}
+
+async fn run_subcommand(
+    command: Commands,
+    node: &mut VeilidNode,
+    missing_directories: Vec<PathBuf>,
+) -> Result<(), anyhow::Error> {
     match command {
-        Commands::Init => commands::init::run(),
-        Commands::Install => commands::install::run(),
-        Commands::Library => commands::library::run(),
+        Commands::Blob { command } => commands::blob::run(command, node).await,
+        Commands::Config { command } => commands::config::run(command),
+        Commands::Generations { command } => commands::generations::run(command),
+        Commands::Hold { package } => commands::hold::run(package),
+        Commands::Info {
+            package,
+            library,
+            script,
+        } => commands::info::run(node, package, library, script).await,
+        Commands::Init => commands::init::run(missing_directories),
+        Commands::Install {
+            package,
+            library,
+            script,
+            insecure,
+            build_from_source,
+        } => {
+            commands::install::run(node, package, library, script, insecure, build_from_source)
+                .await
+        }
+        Commands::Library { command } => commands::library::run(command, node).await,
+        Commands::List { filters } => commands::list::run(filters),
+        Commands::Pin {
+            package,
+            requirement,
+        } => commands::pin::run(package, requirement),
         Commands::Pride => commands::pride::run(),
-        Commands::Publish => commands::publish::run(),
-        Commands::Search => commands::search::run(),
-        Commands::Uninstall => commands::uninstall::run(),
-        Commands::Upgrade => commands::upgrade::run(),
+        Commands::Publish {
+            script,
+            library,
+            name,
+            force,
+            prebuilt,
+        } => commands::publish::run(node, &script, library, name, force, prebuilt).await,
+        Commands::Rollback { package, to } => commands::rollback::run(package, to),
+        Commands::Search { options } => commands::search::run(options),
+        Commands::Status => commands::status::run(node).await,
+        Commands::Unhold { package } => commands::unhold::run(package),
+        Commands::Uninstall { package } => commands::uninstall::run(package),
+        Commands::Unpin { package } => commands::unpin::run(package),
+        Commands::Upgrade {
+            packages,
+            dry_run,
+            yes,
+            insecure,
+            build_from_source,
+        } => {
+            commands::upgrade::run(node, packages, dry_run, yes, insecure, build_from_source).await
+        }
     }
 }
 
-/// update_callback() is called every time if something interesting happens with veilid.
-fn update_callback(_update: VeilidUpdate) {}
+/// command_name() returns the name of the subcommand including its nested subcommands, e.g.
+/// "library sync".
+fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, matches)) = current.subcommand() {
+        names.push(name);
+        current = matches;
+    }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    let cli = Cli::parse();
+    names.join(" ")
+}
+
+/// run() sets bote up for the invocation and runs the subcommand.
+async fn run(cli: Cli) -> Result<(), anyhow::Error> {
+    if let Some(home) = cli.home {
+        config::set_home(home);
+    }
+    if cli.xdg {
+        config::set_use_xdg(true);
+    }
+    settings::set_command_line_overrides(cli.settings);
+    settings::validate_overrides()?;
 
+    // the logger creates the state directory, so init has to know what was missing before
+    let missing_directories = match cli.command {
+        Some(Commands::Init) => commands::init::missing_directories()?,
+        _ => Vec::new(),
+    };
     logging::setup_logger(cli.verbosity)?;
 
     if cli.clear_log_file {
-        std::fs::remove_file(get_app_directory()? + "/bote.log")?;
+        std::fs::remove_file(get_log_file_path()?)?;
     }
 
-    if let Some(command) = cli.command {
-        run_subcommand(command)?;
-    }
+    let mut node = VeilidNode::new();
 
-    //let update_callback = Arc::new(update_callback);
-    //let config_callback = Arc::new(config_callback);
-    //let api = veilid_core::api_startup(update_callback, config_callback).await?;
+    let result = match cli.command {
+        // biased makes sure the subcommand is polled first. Subcommands that don't use the network
+        // finish during the first poll, so the Ctrl-C handler is only installed while veilid may
+        // be running. Build scripts run on a blocking thread, so they don't keep the handler from
+        // firing.
+        Some(command) => tokio::select! {
+            biased;
+            result = run_subcommand(command, &mut node, missing_directories) => result,
+            _ = tokio::signal::ctrl_c() => Err(Error::Interrupted.into()),
+        },
+        None => Ok(()),
+    };
 
-    //api.attach().await?;
+    node.shutdown().await;
 
-    //info!("Connected to veilid");
+    result
+}
 
-    //api.detach().await?;
-    //api.shutdown().await;
+/// main() runs bote and exits with the exit code of the class of the error if it fails, see
+/// ErrorKind.
+#[tokio::main]
+async fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    info!("Disconnected from veilid");
+    output::set_format(cli.output);
+    let result = run(cli).await;
 
-    Ok(())
+    if output::is_json() {
+        if let Err(e) = output::print_document(&command_name(&matches), &result) {
+            eprintln!("Error: failed to print the JSON document: {}", e);
+        }
+    }
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            let kind = ErrorKind::of(&e);
+            if kind == ErrorKind::Interrupted {
+                // a build script may still run on a blocking thread, which the runtime would wait
+                // for when it is dropped
+                std::process::exit(kind.exit_code().into());
+            }
+            ExitCode::from(kind.exit_code())
+        }
+    }
 }
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use tokio::sync::broadcast;
+use veilid_core::{
+    AttachmentState, CryptoSystemVersion, RoutingContext, VeilidAPI, VeilidValueChange,
+};
+
+use crate::config::config_callback;
+use crate::error::Error;
+use crate::settings::Settings;
+use crate::status::{update_callback, NodeStatus, SharedStatus};
+
+/// ATTACHMENT_POLL_INTERVAL is the time between two checks of the attachment state.
+const ATTACHMENT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// VALUE_CHANGE_CAPACITY is the number of value changes that are buffered for every subscriber.
+const VALUE_CHANGE_CAPACITY: usize = 64;
+
+/// A veilid node that is started lazily. Subcommands that need the network call api() and the
+/// node is only started the first time that happens, so subcommands that work offline never
+/// touch veilid. Subcommands that only need cryptography or the local state of veilid call
+/// crypto() or start(), which start veilid without attaching it to the network.
+pub struct VeilidNode {
+    status: SharedStatus,
+    value_changes: broadcast::Sender<VeilidValueChange>,
+    api: Option<VeilidAPI>,
+    attaching: bool,
+    attached: bool,
+}
+
+impl Default for VeilidNode {
+    fn default() -> Self {
+        Self {
+            status: SharedStatus::default(),
+            value_changes: broadcast::channel(VALUE_CHANGE_CAPACITY).0,
+            api: None,
+            attaching: false,
+            attached: false,
+        }
+    }
+}
+
+impl VeilidNode {
+    /// new() creates a node that isn't started yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// subscribe_value_changes() returns a receiver for the DHT value changes veilid reports.
+    pub fn subscribe_value_changes(&self) -> broadcast::Receiver<VeilidValueChange> {
+        self.value_changes.subscribe()
+    }
+
+    /// status() returns a snapshot of the status veilid reported.
+    pub fn status(&self) -> NodeStatus {
+        self.status
+            .read()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    /// api() returns the veilid API. The node is started, attached to the network and waits
+    /// until it is attached well enough for DHT operations if this didn't happen yet. If the
+    /// attachment times out, the node keeps running, so the state it reached can still be read
+    /// with start().
+    pub async fn api(&mut self) -> Result<&VeilidAPI, anyhow::Error> {
+        let api = self.start().await?.clone();
+
+        if !self.attached {
+            let timeout = Duration::from_secs(Settings::load()?.attachment_timeout);
+            self.attaching = true;
+            if let Err(e) = attach(&api, timeout).await {
+                if !matches!(e.downcast_ref(), Some(Error::AttachmentTimeout { .. })) {
+                    self.shutdown().await;
+                }
+                return Err(e);
+            }
+            self.attached = true;
+        }
+
+        Ok(self.api.as_ref().expect("the node was started"))
+    }
+
+    /// crypto() returns the best crypto system of veilid. Veilid is started if this didn't
+    /// happen yet, but it isn't attached to the network, so this works offline.
+    pub async fn crypto(&mut self) -> Result<CryptoSystemVersion, anyhow::Error> {
+        Ok(self.start().await?.crypto()?.best())
+    }
+
+    /// routing_context() returns a routing context of the started node.
+    pub async fn routing_context(&mut self) -> Result<RoutingContext, anyhow::Error> {
+        Ok(self.api().await?.routing_context()?)
+    }
+
+    /// is_started() returns whether the node was started.
+    pub fn is_started(&self) -> bool {
+        self.api.is_some()
+    }
+
+    /// start() starts veilid with the merged settings if this didn't happen yet and returns the
+    /// veilid API. The node isn't attached to the network, so this works offline.
+    pub async fn start(&mut self) -> Result<&VeilidAPI, anyhow::Error> {
+        if self.api.is_none() {
+            info!("Starting veilid...");
+            let api = veilid_core::api_startup(
+                update_callback(self.status.clone(), self.value_changes.clone()),
+                config_callback(Settings::load()?.veilid_settings()?),
+            )
+            .await?;
+            self.api = Some(api);
+        }
+
+        Ok(self.api.as_ref().expect("the node was started"))
+    }
+
+    /// shutdown() detaches the node from the network and shuts veilid down. Nothing happens if
+    /// the node was never started.
+    pub async fn shutdown(&mut self) {
+        let Some(api) = self.api.take() else {
+            return;
+        };
+
+        self.attached = false;
+        if std::mem::take(&mut self.attaching) {
+            if let Err(e) = api.detach().await {
+                warn!("Failed to detach from veilid: {}", e);
+            }
+        }
+        api.shutdown().await;
+
+        info!("Disconnected from veilid");
+    }
+}
+
+/// attach() attaches veilid to the network and waits until the attachment is good enough for DHT
+/// operations.
+async fn attach(api: &VeilidAPI, timeout: Duration) -> Result<(), anyhow::Error> {
+    api.attach().await.map_err(|e| Error::NetworkUnavailable {
+        reason: e.to_string(),
+    })?;
+
+    let wait = async {
+        loop {
+            let state = api.get_state().await?;
+            debug!("Attachment state: {:?}", state.attachment.state);
+
+            if is_ready_for_dht(&state.attachment.state) && state.attachment.public_internet_ready {
+                return Ok::<(), anyhow::Error>(());
+            }
+
+            tokio::time::sleep(ATTACHMENT_POLL_INTERVAL).await;
+        }
+    };
+
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => result?,
+        Err(_) => {
+            return Err(Error::AttachmentTimeout {
+                seconds: timeout.as_secs(),
+            }
+            .into())
+        }
+    }
+
+    info!("Connected to veilid");
+
+    Ok(())
+}
+
+/// is_ready_for_dht() returns whether an attachment state allows DHT operations.
+pub fn is_ready_for_dht(state: &AttachmentState) -> bool {
+    matches!(
+        state,
+        AttachmentState::AttachedWeak
+            | AttachmentState::AttachedGood
+            | AttachmentState::AttachedStrong
+            | AttachmentState::FullyAttached
+            | AttachmentState::OverAttached
+    )
+}
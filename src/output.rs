@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Error, ErrorKind, ScriptError};
+
+/// OUTPUT_SCHEMA_VERSION is the version of the JSON output. It is increased whenever a field is
+/// removed or changes its meaning. New fields can be added without increasing it, so tools must
+/// ignore fields they don't know.
+pub const OUTPUT_SCHEMA_VERSION: u32 = 1;
+
+/// The formats bote can print the results of subcommands in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text on stdout.
+    #[default]
+    Human,
+    /// A single JSON document on stdout, see Document. Human readable text goes to stderr.
+    Json,
+}
+
+/// JSON_OUTPUT is set if the output format is JSON.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// RESULT contains the structured result of the running subcommand.
+static RESULT: Mutex<Option<Value>> = Mutex::new(None);
+
+/// The JSON document bote prints to stdout if the output format is JSON. Exactly one document is
+/// printed per invocation, e.g.
+///
+/// `{"schema_version": 1, "command": "library sync", "status": "ok", "result": [...]}`
+///
+/// `{"schema_version": 1, "command": "install", "status": "error", "error": {"kind":
+/// "package_not_found", "exit_code": 4, "message": "...", "causes": [...]}}`
+///
+/// The result of every subcommand is documented at the type it is serialized from. It is null for
+/// subcommands without a result.
+#[derive(Serialize, Debug)]
+pub struct Document {
+    /// The version of the format, see OUTPUT_SCHEMA_VERSION.
+    pub schema_version: u32,
+    /// The subcommand including its nested subcommands, e.g. "library sync".
+    pub command: String,
+    /// Whether the subcommand succeeded.
+    pub status: Status,
+    /// The result of the subcommand if it succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error if the subcommand failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorOutput>,
+}
+
+/// The status of a finished subcommand.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Error,
+}
+
+/// An error as it is printed in JSON documents.
+#[derive(Serialize, Debug)]
+pub struct ErrorOutput {
+    /// The class of the error, see ErrorKind.
+    pub kind: ErrorKind,
+    /// The exit code bote exits with because of the error.
+    pub exit_code: u8,
+    /// The message of the error.
+    pub message: String,
+    /// The messages of the errors that caused it, outermost first.
+    pub causes: Vec<String>,
+    /// The location of the error in the build script, if a build script failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<ScriptError>,
+}
+
+impl From<&anyhow::Error> for ErrorOutput {
+    fn from(error: &anyhow::Error) -> Self {
+        let kind = ErrorKind::of(error);
+
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            message: error.to_string(),
+            causes: error
+                .chain()
+                .skip(1)
+                .map(|cause| cause.to_string())
+                .collect(),
+            script: error
+                .chain()
+                .find_map(|cause| match cause.downcast_ref::<Error>() {
+                    Some(Error::Script(script)) => Some(script.as_ref().clone()),
+                    _ => None,
+                }),
+        }
+    }
+}
+
+/// set_format() sets the output format for this invocation.
+pub fn set_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+/// is_json() returns whether the output format is JSON.
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// set_result() sets the structured result of the running subcommand. It replaces a result that
+/// was set before.
+pub fn set_result<T: Serialize>(result: &T) -> Result<(), serde_json::Error> {
+    let result = serde_json::to_value(result)?;
+
+    if let Ok(mut current) = RESULT.lock() {
+        *current = Some(result);
+    }
+
+    Ok(())
+}
+
+/// print_document() prints the JSON document of a finished subcommand to stdout.
+pub fn print_document(
+    command: &str,
+    result: &Result<(), anyhow::Error>,
+) -> Result<(), serde_json::Error> {
+    let document = match result {
+        Ok(()) => Document {
+            schema_version: OUTPUT_SCHEMA_VERSION,
+            command: command.to_string(),
+            status: Status::Ok,
+            result: Some(
+                RESULT
+                    .lock()
+                    .ok()
+                    .and_then(|mut result| result.take())
+                    .unwrap_or(Value::Null),
+            ),
+            error: None,
+        },
+        Err(e) => Document {
+            schema_version: OUTPUT_SCHEMA_VERSION,
+            command: command.to_string(),
+            status: Status::Error,
+            result: None,
+            error: Some(e.into()),
+        },
+    };
+
+    println!("{}", serde_json::to_string(&document)?);
+
+    Ok(())
+}
+
+/// human!() prints a line for humans like println!(). If the output format is JSON, the line goes
+/// to stderr, so stdout only contains the JSON document.
+#[macro_export]
+macro_rules! human {
+    ($($arg:tt)*) => {
+        if $crate::output::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// A reference to a package inside of a library, as returned by the bote_dependencies() and
+/// conflicts() functions of a build script.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PackageReference {
+    /// The DHT key of the library that contains the package.
+    pub library: String,
+    /// The name of the package.
+    pub name: String,
+    /// An optional version requirement the package has to satisfy, like ">=1.2, <2".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirement: Option<String>,
+}
+
+impl Display for PackageReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.library, self.name)
+    }
+}
+
+/// parse_package_references() converts the array of arrays returned by a build script into
+/// package references. Every inner array contains the library and the package name and
+/// optionally a version requirement. Empty inner arrays are skipped, since build scripts without
+/// any dependencies return [[]].
+pub fn parse_package_references(array: rhai::Array) -> Result<Vec<PackageReference>, Error> {
+    let mut references = Vec::new();
+
+    for entry in array {
+        let entry_string = entry.to_string();
+        let entry = entry.try_cast::<rhai::Array>().ok_or(Error::Conversion {
+            from: entry_string.clone(),
+            into: "[library, package, requirement]".to_string(),
+        })?;
+
+        match entry.as_slice() {
+            [] => continue,
+            [library, name] => references.push(PackageReference {
+                library: library.to_string(),
+                name: name.to_string(),
+                requirement: None,
+            }),
+            [library, name, requirement] => references.push(PackageReference {
+                library: library.to_string(),
+                name: name.to_string(),
+                requirement: Some(requirement.to_string()),
+            }),
+            _ => {
+                return Err(Error::Conversion {
+                    from: entry_string,
+                    into: "[library, package, requirement]".to_string(),
+                })
+            }
+        }
+    }
+
+    Ok(references)
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::database::{InstalledDatabase, InstalledPackage};
+use crate::error::{with_path, Error};
+use crate::settings::Settings;
+use crate::store;
+
+/// GENERATION_FILE is the name of the file inside of a generation that describes it.
+const GENERATION_FILE: &str = "generation.json";
+
+/// A generation of the profile. Every generation is a directory of symlinks into the store paths
+/// of the packages that were installed when it was created.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Generation {
+    /// The number of the generation. Newer generations have higher numbers.
+    pub number: u64,
+    /// The time the generation was created in seconds since the unix epoch.
+    pub created_at: u64,
+    /// The packages that are part of the generation.
+    pub packages: Vec<InstalledPackage>,
+}
+
+/// get_generation_path() returns the directory of a generation.
+fn get_generation_path(number: u64) -> Result<PathBuf, Error> {
+    Ok(config::get_profiles_directory()?.join(number.to_string()))
+}
+
+/// read_generation() reads the description of a generation.
+pub fn read_generation(number: u64) -> Result<Generation, anyhow::Error> {
+    let path = get_generation_path(number)?.join(GENERATION_FILE);
+    if !path.exists() {
+        return Err(Error::NotFound {
+            whats_missing: format!("generation {}", number),
+        }
+        .into());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// generations() returns all generations ordered by their number.
+pub fn generations() -> Result<Vec<Generation>, anyhow::Error> {
+    let profiles_directory = config::get_profiles_directory()?;
+    let mut generations = Vec::new();
+
+    if !profiles_directory.exists() {
+        return Ok(generations);
+    }
+
+    for entry in fs::read_dir(profiles_directory)? {
+        if let Ok(number) = entry?.file_name().to_string_lossy().parse::<u64>() {
+            generations.push(read_generation(number)?);
+        }
+    }
+
+    generations.sort_by_key(|generation| generation.number);
+
+    Ok(generations)
+}
+
+/// current_generation() returns the number of the active generation.
+pub fn current_generation() -> Result<Option<u64>, anyhow::Error> {
+    let profile = config::get_profile_path()?;
+    if profile.symlink_metadata().is_err() {
+        return Ok(None);
+    }
+
+    let target = fs::read_link(profile)?;
+    Ok(target
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse().ok()))
+}
+
+/// create_generation() creates a new generation from the packages of the installed package
+/// database and switches to it.
+pub fn create_generation(database: &InstalledDatabase) -> Result<u64, anyhow::Error> {
+    let profiles_directory = config::get_profiles_directory()?;
+    fs::create_dir_all(&profiles_directory).map_err(|e| with_path(e, &profiles_directory))?;
+
+    let number = generations()?
+        .last()
+        .map(|generation| generation.number + 1)
+        .unwrap_or(1);
+
+    // the generation is built in a temporary directory, so a half built generation never exists
+    let building = profiles_directory.join(format!(".building-{}", number));
+    if building.exists() {
+        fs::remove_dir_all(&building)?;
+    }
+    fs::create_dir_all(&building)?;
+
+    for package in database.packages() {
+        let manifest = store::read_manifest(&package.name, &package.version)?;
+        let tree = store::get_tree_path(&store::get_store_path(&package.name, &package.version)?);
+
+        for file in &manifest.files {
+            let link = building.join(file);
+            if link.symlink_metadata().is_ok() {
+                warn!(
+                    "{} of {} conflicts with a file of another package and is not linked",
+                    file.display(),
+                    package.name
+                );
+                continue;
+            }
+
+            if let Some(parent) = link.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            symlink(tree.join(file), &link)?;
+        }
+    }
+
+    let generation = Generation {
+        number,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        packages: database.packages().cloned().collect(),
+    };
+    fs::write(
+        building.join(GENERATION_FILE),
+        serde_json::to_string_pretty(&generation)?,
+    )?;
+    fs::rename(&building, get_generation_path(number)?)?;
+    debug!("Created generation {}", number);
+
+    switch(number)?;
+
+    Ok(number)
+}
+
+/// switch() atomically replaces the profile symlink so it points to another generation.
+pub fn switch(number: u64) -> Result<Generation, anyhow::Error> {
+    let generation = read_generation(number)?;
+
+    let profile = config::get_profile_path()?;
+    let temporary_link = profile.with_extension("tmp");
+    if temporary_link.symlink_metadata().is_ok() {
+        fs::remove_file(&temporary_link)?;
+    }
+    symlink(get_generation_path(number)?, &temporary_link)?;
+    fs::rename(&temporary_link, profile)?;
+
+    info!("Switched to generation {}", number);
+
+    Ok(generation)
+}
+
+/// delete() deletes a generation. The active generation can't be deleted.
+pub fn delete(number: u64) -> Result<(), anyhow::Error> {
+    if current_generation()? == Some(number) {
+        return Err(Error::ActiveGeneration { number }.into());
+    }
+
+    read_generation(number)?;
+    fs::remove_dir_all(get_generation_path(number)?)?;
+    info!("Deleted generation {}", number);
+
+    Ok(())
+}
+
+/// commit() saves the installed package database, creates a new generation from it and removes
+/// package versions that exceed the configured number of kept versions.
+pub fn commit(database: &InstalledDatabase) -> Result<(), anyhow::Error> {
+    database.save()?;
+    create_generation(database)?;
+    collect_garbage(Settings::load()?.keep_versions)
+}
+
+/// collect_garbage() removes old package versions from the store so that every package only has
+/// its active version and `keep` previous versions. Generations that use a removed version are
+/// deleted as well, since they couldn't be switched to anymore.
+fn collect_garbage(keep: usize) -> Result<(), anyhow::Error> {
+    let current = match current_generation()? {
+        Some(number) => read_generation(number)?.packages,
+        None => Vec::new(),
+    };
+    let active: BTreeSet<(String, String)> = current
+        .into_iter()
+        .map(|package| (package.name, package.version))
+        .collect();
+
+    let names: BTreeSet<String> = store::manifests()?
+        .into_iter()
+        .map(|manifest| manifest.package.name)
+        .collect();
+
+    for name in names {
+        let previous_versions = store::kept_versions(&name)?
+            .into_iter()
+            .filter(|manifest| !active.contains(&(name.clone(), manifest.package.version.clone())));
+
+        for manifest in previous_versions.skip(keep) {
+            let version = manifest.package.version;
+
+            for generation in generations()? {
+                let uses_version = generation
+                    .packages
+                    .iter()
+                    .any(|package| package.name == name && package.version == version);
+                if uses_version {
+                    delete(generation.number)?;
+                }
+            }
+
+            info!(
+                "Removing old version {} of {} from the store",
+                version, name
+            );
+            store::remove(&name, &version)?;
+        }
+    }
+
+    Ok(())
+}
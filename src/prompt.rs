@@ -0,0 +1,20 @@
+use std::io::{self, BufRead, Write};
+
+use crate::output;
+
+/// confirm() asks the user a yes/no question on the terminal. Everything except an explicit
+/// "y" or "yes" is treated as no. The question is asked on stderr if the output format is JSON.
+pub fn confirm(question: &str) -> Result<bool, anyhow::Error> {
+    if output::is_json() {
+        eprint!("{} [y/N] ", question);
+        io::stderr().flush()?;
+    } else {
+        print!("{} [y/N] ", question);
+        io::stdout().flush()?;
+    }
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
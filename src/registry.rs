@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::error::Error;
+
+/// A library that was imported by the user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedLibrary {
+    /// The local name of the library, used to address its packages as <alias>/<package>.
+    pub alias: String,
+    /// The DHT key of the library record.
+    pub key: String,
+    /// The name of the library from its header.
+    pub name: String,
+    /// The description of the library from its header.
+    #[serde(default)]
+    pub description: String,
+    /// The time of the last sync in seconds since the unix epoch. This is None if the library
+    /// was never synced.
+    #[serde(default)]
+    pub last_sync: Option<u64>,
+    /// Whether the user trusts the maintainers of the library.
+    #[serde(default)]
+    pub trusted: bool,
+}
+
+/// The registry of all imported libraries.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Registry {
+    libraries: BTreeMap<String, ImportedLibrary>,
+}
+
+impl Registry {
+    /// load() loads the registry of imported libraries. An empty registry is returned if no
+    /// library was imported yet.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = config::get_library_registry_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// save() writes the registry to disk. The registry is written to a temporary file first
+    /// which then replaces the old registry, so a failed write never corrupts it.
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = config::get_library_registry_path()?;
+        fs::create_dir_all(config::get_database_directory()?)?;
+
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(&temporary_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temporary_path, &path)?;
+
+        Ok(())
+    }
+
+    /// get() returns an imported library by its alias.
+    pub fn get(&self, alias: &str) -> Option<&ImportedLibrary> {
+        self.libraries.get(alias)
+    }
+
+    /// get_mut() returns a mutable imported library by its alias.
+    pub fn get_mut(&mut self, alias: &str) -> Option<&mut ImportedLibrary> {
+        self.libraries.get_mut(alias)
+    }
+
+    /// get_by_key() returns an imported library by its DHT key.
+    pub fn get_by_key(&self, key: &str) -> Option<&ImportedLibrary> {
+        self.libraries.values().find(|library| library.key == key)
+    }
+
+    /// resolve() returns an imported library by its alias or its DHT key.
+    pub fn resolve(&self, library: &str) -> Result<&ImportedLibrary, Error> {
+        self.get(library)
+            .or_else(|| self.get_by_key(library))
+            .ok_or(Error::NotFound {
+                whats_missing: format!("imported library {}", library),
+            })
+    }
+
+    /// is_trusted() returns whether the user trusts the maintainers of a library given by its DHT
+    /// key. Libraries that weren't imported are never trusted.
+    pub fn is_trusted(&self, key: &str) -> bool {
+        self.get_by_key(key).is_some_and(|library| library.trusted)
+    }
+
+    /// canonical_key() returns the DHT key of a library that is given by its alias or its DHT
+    /// key. Unknown libraries are returned unchanged, since they are expected to be DHT keys of
+    /// libraries that weren't imported yet.
+    pub fn canonical_key(&self, library: &str) -> String {
+        self.get(library)
+            .map(|library| library.key.clone())
+            .unwrap_or_else(|| library.to_string())
+    }
+
+    /// check_alias() returns an error if an alias is invalid or already used by another library.
+    pub fn check_alias(&self, alias: &str) -> Result<(), Error> {
+        if alias.is_empty() || alias.contains('/') {
+            return Err(Error::InvalidAlias {
+                alias: alias.to_string(),
+            });
+        }
+
+        if let Some(existing) = self.get(alias) {
+            return Err(Error::AliasTaken {
+                alias: alias.to_string(),
+                library: existing.key.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// insert() adds an imported library. An error is returned if its alias is invalid or
+    /// already used by another library.
+    pub fn insert(&mut self, library: ImportedLibrary) -> Result<(), Error> {
+        self.check_alias(&library.alias)?;
+
+        self.libraries.insert(library.alias.clone(), library);
+        Ok(())
+    }
+
+    /// remove() removes an imported library by its alias.
+    pub fn remove(&mut self, alias: &str) -> Option<ImportedLibrary> {
+        self.libraries.remove(alias)
+    }
+
+    /// libraries() returns all imported libraries ordered by their alias.
+    pub fn libraries(&self) -> impl Iterator<Item = &ImportedLibrary> {
+        self.libraries.values()
+    }
+}
+
+/// parse_package_address() splits a package address of the form <alias>/<package> into the
+/// library and the package name. The library is None if the address is a plain package name.
+pub fn parse_package_address(address: &str) -> (Option<&str>, &str) {
+    match address.split_once('/') {
+        Some((library, package)) => (Some(library), package),
+        None => (None, address),
+    }
+}
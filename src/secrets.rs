@@ -0,0 +1,82 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use veilid_core::{CryptoSystem, KeyPair, VeilidAPI};
+
+use crate::error::Error;
+
+/// IDENTITY_SECRET is the name of the protected store entry that contains the writer keypair of
+/// this bote installation.
+const IDENTITY_SECRET: &str = "identity";
+
+/// get_library_owner_secret() returns the name of the protected store entry that contains the
+/// owner keypair of a library.
+fn get_library_owner_secret(library: &str) -> String {
+    format!("library_owner_{}", library)
+}
+
+/// protected_store_error() converts an error of the protected store into a bote error.
+fn protected_store_error(error: impl Display) -> Error {
+    Error::ProtectedStore {
+        message: error.to_string(),
+    }
+}
+
+/// parse_keypair() parses a keypair that was read from the protected store.
+fn parse_keypair(keypair: &str) -> Result<KeyPair, Error> {
+    KeyPair::from_str(keypair).map_err(|_| Error::Conversion {
+        from: "stored secret".to_string(),
+        into: "keypair".to_string(),
+    })
+}
+
+/// save_library_owner() stores the owner keypair of a library in the protected store.
+pub async fn save_library_owner(
+    api: &VeilidAPI,
+    library: &str,
+    owner: &KeyPair,
+) -> Result<(), anyhow::Error> {
+    api.protected_store()?
+        .save_user_secret_string(get_library_owner_secret(library), owner.to_string())
+        .await
+        .map_err(protected_store_error)?;
+
+    Ok(())
+}
+
+/// load_library_owner() returns the owner keypair of a library if it is in the protected store.
+pub async fn load_library_owner(
+    api: &VeilidAPI,
+    library: &str,
+) -> Result<Option<KeyPair>, anyhow::Error> {
+    let owner = api
+        .protected_store()?
+        .load_user_secret_string(get_library_owner_secret(library))
+        .await
+        .map_err(protected_store_error)?;
+
+    Ok(owner.as_deref().map(parse_keypair).transpose()?)
+}
+
+/// identity() returns the writer keypair of this bote installation. Other library owners can
+/// add its public key as a writer of their libraries. The keypair is generated the first time
+/// it is needed.
+pub async fn identity(api: &VeilidAPI) -> Result<KeyPair, anyhow::Error> {
+    let protected_store = api.protected_store()?;
+
+    if let Some(identity) = protected_store
+        .load_user_secret_string(IDENTITY_SECRET)
+        .await
+        .map_err(protected_store_error)?
+    {
+        return Ok(parse_keypair(&identity)?);
+    }
+
+    let identity = api.crypto()?.best().generate_keypair();
+    protected_store
+        .save_user_secret_string(IDENTITY_SECRET, identity.to_string())
+        .await
+        .map_err(protected_store_error)?;
+
+    Ok(identity)
+}
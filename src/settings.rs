@@ -0,0 +1,511 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::RwLock;
+
+use anyhow::Context;
+use log::warn;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::{self, VeilidSetting};
+use crate::error::Error;
+
+/// VEILID_PREFIX is the prefix of all keys that refer to veilid settings.
+const VEILID_PREFIX: &str = "veilid.";
+
+/// ENVIRONMENT_PREFIX is the prefix of the environment variables that override settings.
+const ENVIRONMENT_PREFIX: &str = "BOTE_";
+
+/// COMMAND_LINE_OVERRIDES contains the settings given with the --set flag.
+static COMMAND_LINE_OVERRIDES: RwLock<Vec<(String, String)>> = RwLock::new(Vec::new());
+
+/// Where the value of a setting comes from. Later origins take precedence over earlier ones:
+/// default < file < environment < command line.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Default,
+    File,
+    Environment,
+    CommandLine,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File => write!(f, "file"),
+            Origin::Environment => write!(f, "env"),
+            Origin::CommandLine => write!(f, "cli"),
+        }
+    }
+}
+
+/// A setting with its effective value.
+#[derive(Serialize, Debug, Clone)]
+pub struct Entry {
+    /// The key of the setting. Veilid settings are prefixed with "veilid.".
+    pub key: String,
+    /// The effective value of the setting.
+    pub value: Value,
+    /// Where the effective value comes from.
+    pub origin: Origin,
+}
+
+/// The settings of bote that are read from the configuration file (~/.bote/config.json). Every
+/// setting that is missing from the file keeps its default value.
+///
+/// The veilid section overrides the built-in veilid defaults key by key, e.g.
+/// `{"veilid": {"network.upnp": false, "network.routing_table.bootstrap": ["example.com"]}}`.
+///
+/// Every setting can also be overridden with an environment variable (see
+/// get_environment_variable()) or with `--set key=value` on the command line. The precedence is
+/// default < file < environment < command line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// The number of previous versions of each package that are kept in the store for rollbacks.
+    /// This doesn't limit the number of generations, but generations that use a removed version
+    /// are deleted together with it.
+    pub keep_versions: usize,
+    /// The number of seconds bote waits for veilid to attach to the network.
+    pub attachment_timeout: u64,
+    /// Overrides of the built-in veilid settings. See config::default_veilid_settings() for all
+    /// available keys.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub veilid: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            keep_versions: 3,
+            attachment_timeout: 30,
+            veilid: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// load() loads the settings from the configuration file and applies the overrides from
+    /// environment variables and the command line. Settings that aren't set anywhere keep their
+    /// default. Unknown keys and values of the wrong type are rejected.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let mut file = read_file()?;
+        for (key, value, _) in overrides()? {
+            insert_value(&mut file, &key, value);
+        }
+
+        let settings: Self = serde_json::from_value(Value::Object(file)).with_context(|| {
+            format!(
+                "invalid configuration file {}",
+                config::get_config_file_path()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default()
+            )
+        })?;
+        settings.veilid_settings()?;
+
+        Ok(settings)
+    }
+
+    /// veilid_settings() merges the veilid overrides with the built-in defaults. The result is
+    /// what config::config_callback() hands to veilid.
+    pub fn veilid_settings(&self) -> Result<BTreeMap<String, VeilidSetting>, Error> {
+        let mut settings = config::default_veilid_settings()?;
+
+        for (key, value) in &self.veilid {
+            let default = settings.get(key).ok_or(Error::UnknownSetting {
+                key: format!("veilid.{}", key),
+            })?;
+            let setting = default
+                .with_json_value(value)
+                .ok_or(Error::InvalidSetting {
+                    key: format!("veilid.{}", key),
+                    expected: default.type_name().to_string(),
+                })?;
+            settings.insert(key.clone(), setting);
+        }
+
+        Ok(settings)
+    }
+}
+
+/// set_command_line_overrides() registers the settings given with the --set flag. They take
+/// precedence over everything else.
+pub fn set_command_line_overrides(overrides: Vec<(String, String)>) {
+    if let Ok(mut command_line_overrides) = COMMAND_LINE_OVERRIDES.write() {
+        *command_line_overrides = overrides;
+    }
+}
+
+/// get_environment_variable() returns the name of the environment variable that overrides a
+/// setting. The key is uppercased and every dot is replaced with an underscore, so
+/// veilid.network.upnp becomes BOTE_VEILID_NETWORK_UPNP.
+pub fn get_environment_variable(key: &str) -> String {
+    ENVIRONMENT_PREFIX.to_string() + &key.replace('.', "_").to_uppercase()
+}
+
+/// parse_value() parses a value given as text. The text is parsed as JSON if possible and used
+/// as a plain string otherwise, so both `false` and `example.com` work.
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+/// insert_value() sets a setting in a JSON object that has the layout of the configuration
+/// file.
+fn insert_value(file: &mut Map<String, Value>, key: &str, value: Value) {
+    match key.strip_prefix(VEILID_PREFIX) {
+        Some(veilid_key) => {
+            let veilid = file
+                .entry("veilid")
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(veilid) = veilid {
+                veilid.insert(veilid_key.to_string(), value);
+            }
+        }
+        None => {
+            file.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// check_value() type checks the value of a setting against its default.
+fn check_value(key: &str, value: &Value) -> Result<(), Error> {
+    let default = defaults()?
+        .remove(key)
+        .ok_or_else(|| Error::UnknownSetting {
+            key: key.to_string(),
+        })?;
+
+    let valid = match key.strip_prefix(VEILID_PREFIX) {
+        Some(veilid_key) => config::default_veilid_settings()?
+            .get(veilid_key)
+            .and_then(|setting| setting.with_json_value(value))
+            .is_some(),
+        None => same_type(&default, value),
+    };
+    if !valid {
+        return Err(Error::InvalidSetting {
+            key: key.to_string(),
+            expected: expected_type(key, &default)?,
+        });
+    }
+
+    Ok(())
+}
+
+/// expected_type() returns a human readable name of the type a setting has.
+fn expected_type(key: &str, default: &Value) -> Result<String, Error> {
+    let veilid_setting = match key.strip_prefix(VEILID_PREFIX) {
+        Some(veilid_key) => config::default_veilid_settings()?.remove(veilid_key),
+        None => None,
+    };
+
+    Ok(match veilid_setting {
+        Some(setting) => setting.type_name().to_string(),
+        None => json_type_name(default).to_string(),
+    })
+}
+
+/// overrides() returns the settings that are overridden by environment variables and the
+/// command line, ordered by their precedence. Every value is type checked, so invalid
+/// overrides are reported before bote does anything.
+fn overrides() -> Result<Vec<(String, Value, Origin)>, anyhow::Error> {
+    let mut overrides = Vec::new();
+
+    for key in defaults()?.into_keys() {
+        let variable = get_environment_variable(&key);
+        let Ok(value) = std::env::var(&variable) else {
+            continue;
+        };
+
+        let value = parse_value(&value);
+        check_value(&key, &value)
+            .with_context(|| format!("invalid environment variable {}", variable))?;
+        overrides.push((key, value, Origin::Environment));
+    }
+
+    let command_line_overrides = COMMAND_LINE_OVERRIDES
+        .read()
+        .map(|overrides| overrides.clone())
+        .unwrap_or_default();
+    for (key, value) in command_line_overrides {
+        let value = parse_value(&value);
+        check_value(&key, &value).with_context(|| format!("invalid value for --set {}", key))?;
+        overrides.push((key, value, Origin::CommandLine));
+    }
+
+    Ok(overrides)
+}
+
+/// validate_overrides() checks the overrides from environment variables and the command line, so
+/// bote can fail at the startup if one of them is invalid.
+pub fn validate_overrides() -> Result<(), anyhow::Error> {
+    overrides()?;
+    Ok(())
+}
+
+/// read_file() reads the configuration file as a JSON object without applying any defaults.
+fn read_file() -> Result<Map<String, Value>, anyhow::Error> {
+    let path = config::get_config_file_path()?;
+
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+
+    match serde_json::from_str(&fs::read_to_string(&path)?)? {
+        Value::Object(map) => Ok(map),
+        _ => Err(anyhow::anyhow!(
+            "invalid configuration file {}: expected a JSON object",
+            path.display()
+        )),
+    }
+}
+
+/// write_file() validates a JSON object against the settings schema and writes it to the
+/// configuration file.
+fn write_file(file: Map<String, Value>) -> Result<(), anyhow::Error> {
+    let settings: Settings = serde_json::from_value(Value::Object(file.clone()))?;
+    settings.veilid_settings()?;
+
+    fs::create_dir_all(config::get_config_directory()?)?;
+    fs::write(
+        config::get_config_file_path()?,
+        serde_json::to_string_pretty(&file)?,
+    )?;
+
+    Ok(())
+}
+
+/// defaults() returns the default values of all settings. Veilid settings are prefixed with
+/// "veilid.".
+fn defaults() -> Result<BTreeMap<String, Value>, Error> {
+    let mut defaults = BTreeMap::new();
+
+    if let Ok(Value::Object(map)) = serde_json::to_value(Settings::default()) {
+        defaults.extend(map.into_iter().filter(|(key, _)| key != "veilid"));
+    }
+
+    for (key, default) in config::default_veilid_settings()? {
+        defaults.insert(VEILID_PREFIX.to_string() + &key, default.to_json_value());
+    }
+
+    Ok(defaults)
+}
+
+/// entries() returns all settings with their effective values and origins, ordered by their
+/// key.
+pub fn entries() -> Result<Vec<Entry>, anyhow::Error> {
+    let file = read_file()?;
+    let veilid_file = file.get("veilid").and_then(Value::as_object);
+
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    for (key, default) in defaults()? {
+        let file_value = match key.strip_prefix(VEILID_PREFIX) {
+            Some(veilid_key) => veilid_file.and_then(|veilid| veilid.get(veilid_key)),
+            None => file.get(&key),
+        };
+        let (value, origin) = match file_value {
+            Some(value) => (value.clone(), Origin::File),
+            None => (default, Origin::Default),
+        };
+        entries.insert(key.clone(), Entry { key, value, origin });
+    }
+
+    for (key, value, origin) in overrides()? {
+        if let Some(entry) = entries.get_mut(&key) {
+            entry.value = value;
+            entry.origin = origin;
+        }
+    }
+
+    Ok(entries.into_values().collect())
+}
+
+/// get() returns a single setting.
+pub fn get(key: &str) -> Result<Entry, anyhow::Error> {
+    entries()?
+        .into_iter()
+        .find(|entry| entry.key == key)
+        .ok_or_else(|| {
+            Error::UnknownSetting {
+                key: key.to_string(),
+            }
+            .into()
+        })
+}
+
+/// set() changes a setting in the configuration file. The value is parsed as JSON if possible
+/// and used as a plain string otherwise, so both `false` and `example.com` work. The value is
+/// type checked before it is written.
+pub fn set(key: &str, value: &str) -> Result<Value, anyhow::Error> {
+    let value = parse_value(value);
+    check_value(key, &value)?;
+
+    let mut file = read_file()?;
+    insert_value(&mut file, key, value.clone());
+    write_file(file)?;
+
+    let entry = get(key)?;
+    if entry.origin > Origin::File {
+        warn!(
+            "{} is overridden by the {} and stays {}",
+            key,
+            match entry.origin {
+                Origin::Environment =>
+                    "environment variable ".to_string() + &get_environment_variable(key),
+                _ => "command line".to_string(),
+            },
+            entry.value
+        );
+    }
+
+    Ok(value)
+}
+
+/// reset() resets a setting to its default by removing it from the configuration file. Without
+/// a key, every setting is reset.
+pub fn reset(key: Option<&str>) -> Result<(), anyhow::Error> {
+    let Some(key) = key else {
+        return write_file(Map::new());
+    };
+
+    get(key)?;
+
+    let mut file = read_file()?;
+    match key.strip_prefix(VEILID_PREFIX) {
+        Some(veilid_key) => {
+            if let Some(Value::Object(veilid)) = file.get_mut("veilid") {
+                veilid.remove(veilid_key);
+                if veilid.is_empty() {
+                    file.remove("veilid");
+                }
+            }
+        }
+        None => {
+            file.remove(key);
+        }
+    }
+
+    write_file(file)
+}
+
+/// same_type() returns whether two JSON values have the same type. Numbers only have the same
+/// type if both are non-negative integers, since all numeric bote settings are.
+fn same_type(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.is_u64() == b.is_u64(),
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+/// json_type_name() returns a human readable name of the type of a JSON value.
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a non-negative integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// ENVIRONMENT_LOCK serializes the tests that change the home directory, environment
+    /// variables or command line overrides, since they are shared by the whole process.
+    static ENVIRONMENT_LOCK: Mutex<()> = Mutex::new(());
+
+    /// with_home() runs a test with an empty home directory and without any overrides.
+    fn with_home(test: impl FnOnce()) {
+        let _lock = ENVIRONMENT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let home = tempfile::tempdir().unwrap();
+        config::set_home(home.path().to_path_buf());
+        set_command_line_overrides(Vec::new());
+
+        test();
+
+        set_command_line_overrides(Vec::new());
+        for key in defaults().unwrap().into_keys() {
+            std::env::remove_var(get_environment_variable(&key));
+        }
+    }
+
+    #[test]
+    fn environment_variables_are_derived_from_keys() {
+        assert_eq!(
+            get_environment_variable("attachment_timeout"),
+            "BOTE_ATTACHMENT_TIMEOUT"
+        );
+        assert_eq!(
+            get_environment_variable("veilid.network.upnp"),
+            "BOTE_VEILID_NETWORK_UPNP"
+        );
+        assert_eq!(
+            get_environment_variable("veilid.network.routing_table.bootstrap"),
+            "BOTE_VEILID_NETWORK_ROUTING_TABLE_BOOTSTRAP"
+        );
+    }
+
+    #[test]
+    fn overrides_follow_the_precedence() {
+        with_home(|| {
+            let effective = || {
+                let entry = get("attachment_timeout").unwrap();
+                assert_eq!(
+                    Value::from(Settings::load().unwrap().attachment_timeout),
+                    entry.value
+                );
+                (entry.value, entry.origin)
+            };
+
+            assert_eq!(effective(), (Value::from(30), Origin::Default));
+
+            set("attachment_timeout", "40").unwrap();
+            assert_eq!(effective(), (Value::from(40), Origin::File));
+
+            std::env::set_var("BOTE_ATTACHMENT_TIMEOUT", "50");
+            assert_eq!(effective(), (Value::from(50), Origin::Environment));
+
+            set_command_line_overrides(vec![("attachment_timeout".to_string(), "60".to_string())]);
+            assert_eq!(effective(), (Value::from(60), Origin::CommandLine));
+        });
+    }
+
+    #[test]
+    fn environment_variables_override_veilid_settings() {
+        with_home(|| {
+            std::env::set_var("BOTE_VEILID_NETWORK_UPNP", "false");
+
+            let entry = get("veilid.network.upnp").unwrap();
+            assert_eq!(entry.value, Value::Bool(false));
+            assert_eq!(entry.origin, Origin::Environment);
+            assert_eq!(
+                Settings::load().unwrap().veilid.get("network.upnp"),
+                Some(&Value::Bool(false))
+            );
+        });
+    }
+
+    #[test]
+    fn invalid_overrides_fail_at_the_startup() {
+        with_home(|| {
+            std::env::set_var("BOTE_KEEP_VERSIONS", "many");
+            assert!(validate_overrides().is_err());
+            std::env::remove_var("BOTE_KEEP_VERSIONS");
+
+            set_command_line_overrides(vec![("veilid.network.upnp".to_string(), "1".to_string())]);
+            assert!(validate_overrides().is_err());
+
+            set_command_line_overrides(vec![("unknown".to_string(), "1".to_string())]);
+            assert!(validate_overrides().is_err());
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::{Arc, RwLock};
+
+use log::{debug, info, log, Level};
+use tokio::sync::broadcast;
+use veilid_core::{
+    AttachmentState, UpdateCallback, VeilidLogLevel, VeilidUpdate, VeilidValueChange,
+};
+
+/// The status of the veilid node as reported by veilid updates.
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    /// The current attachment state.
+    pub attachment: AttachmentState,
+    /// Whether the node can reach the public internet.
+    pub public_internet_ready: bool,
+    /// Whether the network of the node is started.
+    pub network_started: bool,
+    /// The number of peers the node is connected to.
+    pub peer_count: usize,
+    /// The number of DHT value changes that were reported.
+    pub value_changes: u64,
+}
+
+impl Default for NodeStatus {
+    fn default() -> Self {
+        Self {
+            attachment: AttachmentState::Detached,
+            public_internet_ready: false,
+            network_started: false,
+            peer_count: 0,
+            value_changes: 0,
+        }
+    }
+}
+
+impl NodeStatus {
+    /// progress() returns a short description of the attachment progress, e.g.
+    /// "attaching: 12 peers, weak".
+    pub fn progress(&self) -> String {
+        format!(
+            "attaching: {} peers, {}",
+            self.peer_count,
+            attachment_state_name(&self.attachment)
+        )
+    }
+}
+
+/// The status of the veilid node that is shared between the update callback and bote.
+pub type SharedStatus = Arc<RwLock<NodeStatus>>;
+
+/// attachment_state_name() returns a short human readable name of an attachment state.
+pub fn attachment_state_name(state: &AttachmentState) -> &'static str {
+    match state {
+        AttachmentState::Detached => "detached",
+        AttachmentState::Attaching => "attaching",
+        AttachmentState::AttachedWeak => "weak",
+        AttachmentState::AttachedGood => "good",
+        AttachmentState::AttachedStrong => "strong",
+        AttachmentState::FullyAttached => "fully attached",
+        AttachmentState::OverAttached => "over attached",
+        AttachmentState::Detaching => "detaching",
+    }
+}
+
+/// update_callback() returns the callback veilid calls every time if something interesting
+/// happens. Updates are written to the bote logger and into the shared status, value changes are
+/// also sent to everyone who subscribed to them.
+pub fn update_callback(
+    status: SharedStatus,
+    value_changes: broadcast::Sender<VeilidValueChange>,
+) -> UpdateCallback {
+    Arc::new(move |update| handle_update(&status, &value_changes, update))
+}
+
+/// handle_update() processes a single veilid update.
+fn handle_update(
+    status: &SharedStatus,
+    value_changes: &broadcast::Sender<VeilidValueChange>,
+    update: VeilidUpdate,
+) {
+    let Ok(mut status) = status.write() else {
+        return;
+    };
+
+    match update {
+        VeilidUpdate::Log(veilid_log) => {
+            let level = match veilid_log.log_level {
+                VeilidLogLevel::Error => Level::Error,
+                VeilidLogLevel::Warn => Level::Warn,
+                VeilidLogLevel::Info => Level::Info,
+                VeilidLogLevel::Debug => Level::Debug,
+                VeilidLogLevel::Trace => Level::Trace,
+            };
+            log!(target: "veilid", level, "{}", veilid_log.message);
+        }
+        VeilidUpdate::Attachment(attachment) => {
+            status.attachment = attachment.state;
+            status.public_internet_ready = attachment.public_internet_ready;
+            info!("{}", status.progress());
+        }
+        VeilidUpdate::Network(network) => {
+            let peer_count_changed = status.peer_count != network.peers.len();
+
+            status.network_started = network.started;
+            status.peer_count = network.peers.len();
+
+            if peer_count_changed {
+                info!("{}", status.progress());
+            }
+        }
+        VeilidUpdate::ValueChange(change) => {
+            status.value_changes += 1;
+            debug!("Value of DHT record {} changed", change.key);
+
+            // sending only fails if nobody subscribed, which is fine
+            let _ = value_changes.send(change);
+        }
+        VeilidUpdate::Shutdown => {
+            *status = NodeStatus::default();
+            debug!("Veilid shut down");
+        }
+        _ => {}
+    }
+}
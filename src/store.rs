@@ -0,0 +1,209 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::config;
+use crate::database::InstalledPackage;
+use crate::error::{with_path, Error};
+
+/// The manifest of a store path. It is stored next to the staged tree of a package version, so
+/// the version can be activated again without rebuilding the package.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    /// The database entry of the package version.
+    pub package: InstalledPackage,
+    /// All files of the staged tree, relative to the tree.
+    pub files: Vec<PathBuf>,
+}
+
+/// MANIFEST_FILE is the name of the manifest inside of a store path.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// get_store_path() returns the store path of a package version
+/// (~/.bote/store/<name>@<version>).
+pub fn get_store_path(name: &str, version: &str) -> Result<PathBuf, Error> {
+    let entry = format!("{}@{}", escape(name), escape(version));
+    Ok(config::get_store_directory()?.join(entry))
+}
+
+/// escape() escapes a package name or version for a store path. Names and versions are returned
+/// by build scripts, so every character that could escape the store, hide the store path or
+/// occur in the separator is percent-encoded. Different packages can't share a store path this
+/// way, e.g. foo 1-2 and foo-1 2.
+fn escape(part: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in part.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '~') {
+            escaped.push(c);
+        } else if c == '.' && !escaped.is_empty() {
+            escaped.push(c);
+        } else {
+            let mut buffer = [0; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+
+    escaped
+}
+
+/// get_tree_path() returns the path of the staged tree inside of a store path.
+pub fn get_tree_path(store_path: &Path) -> PathBuf {
+    store_path.join("tree")
+}
+
+/// create_staging_directory() creates a directory that a build script can install a package
+/// into. It is located inside of the store so it can be moved into place without copying.
+pub fn create_staging_directory() -> Result<TempDir, anyhow::Error> {
+    let store_directory = config::get_store_directory()?;
+    fs::create_dir_all(&store_directory).map_err(|e| with_path(e, &store_directory))?;
+
+    Ok(tempfile::Builder::new()
+        .prefix(".staging-")
+        .tempdir_in(store_directory)?)
+}
+
+/// add() moves a staged tree into the store path of its package version and writes the
+/// manifest. The store path is assembled next to the store and moved into place with a single
+/// rename, so it is either complete or missing. Generations link into existing store paths, so
+/// an existing store path of the same version is reused and only its manifest is updated.
+pub fn add(staging: TempDir, package: InstalledPackage) -> Result<Manifest, anyhow::Error> {
+    let store_path = get_store_path(&package.name, &package.version)?;
+    if store_path.exists() {
+        debug!("Reusing existing store path {}", store_path.display());
+        let manifest = Manifest {
+            files: read_manifest(&package.name, &package.version)?.files,
+            package,
+        };
+        write_manifest(&store_path, &manifest)?;
+
+        return Ok(manifest);
+    }
+
+    let adding = tempfile::Builder::new()
+        .prefix(".adding-")
+        .tempdir_in(config::get_store_directory()?)?;
+    let tree = get_tree_path(adding.path());
+    fs::rename(staging.path(), &tree)?;
+
+    let mut files = Vec::new();
+    collect_files(&tree, Path::new(""), &mut files)?;
+    files.sort();
+
+    let manifest = Manifest { package, files };
+    write_manifest(adding.path(), &manifest)?;
+    fs::rename(adding.path(), &store_path)?;
+
+    Ok(manifest)
+}
+
+/// write_manifest() atomically replaces the manifest of a store path.
+fn write_manifest(store_path: &Path, manifest: &Manifest) -> Result<(), anyhow::Error> {
+    let path = store_path.join(MANIFEST_FILE);
+    let temporary = path.with_extension("tmp");
+
+    fs::write(&temporary, serde_json::to_string_pretty(manifest)?)?;
+    fs::rename(temporary, path)?;
+
+    Ok(())
+}
+
+/// collect_files() collects the paths of all files in a directory relative to the root of the
+/// staged tree.
+fn collect_files(
+    directory: &Path,
+    relative: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &relative, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+
+    Ok(())
+}
+
+/// read_manifest() reads the manifest of a package version from the store.
+pub fn read_manifest(name: &str, version: &str) -> Result<Manifest, anyhow::Error> {
+    let path = get_store_path(name, version)?.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Err(Error::NotFound {
+            whats_missing: format!("store path of {} {}", name, version),
+        }
+        .into());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// manifests() returns the manifests of all package versions in the store.
+pub fn manifests() -> Result<Vec<Manifest>, anyhow::Error> {
+    let store_directory = config::get_store_directory()?;
+    let mut manifests = Vec::new();
+
+    if !store_directory.exists() {
+        return Ok(manifests);
+    }
+
+    for entry in fs::read_dir(store_directory)? {
+        let entry = entry?;
+        let manifest = entry.path().join(MANIFEST_FILE);
+        if entry.file_name().to_string_lossy().starts_with('.') || !manifest.exists() {
+            continue;
+        }
+        manifests.push(serde_json::from_str(&fs::read_to_string(manifest)?)?);
+    }
+
+    Ok(manifests)
+}
+
+/// kept_versions() returns the manifests of all versions of a package in the store, the most
+/// recently installed version first.
+pub fn kept_versions(name: &str) -> Result<Vec<Manifest>, anyhow::Error> {
+    let mut manifests: Vec<Manifest> = manifests()?
+        .into_iter()
+        .filter(|manifest| manifest.package.name == name)
+        .collect();
+
+    manifests.sort_by(|a, b| b.package.installed_at.cmp(&a.package.installed_at));
+
+    Ok(manifests)
+}
+
+/// remove() deletes the store path of a package version.
+pub fn remove(name: &str, version: &str) -> Result<(), anyhow::Error> {
+    fs::remove_dir_all(get_store_path(name, version)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_keeps_store_paths_apart() {
+        let entry = |name: &str, version: &str| format!("{}@{}", escape(name), escape(version));
+
+        assert_eq!(entry("ripgrep", "14.1.0"), "ripgrep@14.1.0");
+        assert_ne!(entry("foo", "1-2"), entry("foo-1", "2"));
+        assert_ne!(entry("foo@1", "2"), entry("foo", "1@2"));
+    }
+
+    #[test]
+    fn escape_stays_inside_of_the_store() {
+        assert_eq!(escape("../etc"), "%2E.%2Fetc");
+        assert_eq!(escape(".staging"), "%2Estaging");
+        assert_eq!(escape("a\\b"), "a%5Cb");
+    }
+}
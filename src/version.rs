@@ -0,0 +1,325 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// A package version as returned by the version() function of a build script. Versions are
+/// compared using semver if possible. If one of the versions isn't a valid semver string, the
+/// versions are compared segment by segment instead.
+#[derive(Debug, Clone)]
+pub struct Version {
+    raw: String,
+    semver: Option<semver::Version>,
+}
+
+impl Version {
+    /// parse() parses a version string. Parsing never fails, because versions that aren't valid
+    /// semver are still compared with the fallback ordering.
+    pub fn parse(version: &str) -> Self {
+        let raw = version.trim().to_string();
+        let semver = semver::Version::parse(raw.strip_prefix('v').unwrap_or(&raw)).ok();
+
+        Self { raw, semver }
+    }
+
+    /// as_str() returns the version string as it was returned by the build script.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl From<semver::Version> for Version {
+    fn from(version: semver::Version) -> Self {
+        Self {
+            raw: version.to_string(),
+            semver: Some(version),
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.semver, &other.semver) {
+            (Some(a), Some(b)) => a.cmp(b),
+            _ => compare_segments(&self.raw, &other.raw),
+        }
+    }
+}
+
+/// compare_segments() compares two version strings that aren't valid semver. The strings are
+/// split at every character that isn't alphanumeric and the segments are compared one after
+/// another. Numeric segments are compared by their value and are newer than textual segments,
+/// so "1.10" is newer than "1.9" and "1.0" is newer than "1.0rc".
+fn compare_segments(a: &str, b: &str) -> Ordering {
+    let mut a_segments = version_segments(a);
+    let mut b_segments = version_segments(b);
+
+    loop {
+        match (a_segments.next(), b_segments.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(a), None) => return numeric_ordering(a),
+            (None, Some(b)) => return numeric_ordering(b).reverse(),
+            (Some(a), Some(b)) => {
+                let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    (Ok(_), Err(_)) => Ordering::Greater,
+                    (Err(_), Ok(_)) => Ordering::Less,
+                    (Err(_), Err(_)) => a.cmp(b),
+                };
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// numeric_ordering() decides how a version with an additional segment compares to the same
+/// version without it. Additional numeric segments make a version newer, additional textual
+/// segments mark a pre-release.
+fn numeric_ordering(segment: &str) -> Ordering {
+    if segment.parse::<u64>().is_ok() {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    }
+}
+
+/// version_segments() splits a version string into its numeric and textual segments.
+fn version_segments(version: &str) -> impl Iterator<Item = &str> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+
+    version
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .flat_map(split_numeric)
+        .filter(|segment| !segment.is_empty())
+}
+
+/// split_numeric() splits a segment like "0rc1" into "0", "rc" and "1".
+fn split_numeric(segment: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in segment.char_indices().skip(1) {
+        let previous = segment[..i].chars().last().unwrap_or(c);
+        if previous.is_ascii_digit() != c.is_ascii_digit() {
+            parts.push(&segment[start..i]);
+            start = i;
+        }
+    }
+    parts.push(&segment[start..]);
+
+    parts
+}
+
+/// A version requirement like ">=1.2, <2" that is used for pins and dependency requirements.
+/// Requirements that aren't valid semver requirements, like "2023-01-05", only match the exact
+/// version string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionRequirement {
+    raw: String,
+    semver: Option<semver::VersionReq>,
+}
+
+impl VersionRequirement {
+    /// parse() parses a version requirement. An error is returned if the requirement is neither
+    /// a valid semver requirement nor an exact version.
+    pub fn parse(requirement: &str) -> Result<Self, Error> {
+        let raw = requirement.trim().to_string();
+        let semver = semver::VersionReq::parse(&raw).ok();
+
+        if semver.is_none() && !is_exact_version(&raw) {
+            return Err(Error::InvalidRequirement { requirement: raw });
+        }
+
+        Ok(Self { raw, semver })
+    }
+
+    /// matches() returns whether a version satisfies the requirement.
+    pub fn matches(&self, version: &Version) -> bool {
+        match (&self.semver, &version.semver) {
+            (Some(requirement), Some(version)) => requirement.matches(version),
+            _ => Version::parse(&self.raw) == *version,
+        }
+    }
+
+    /// intersects() returns whether a version can satisfy both requirements.
+    pub fn intersects(&self, other: &VersionRequirement) -> bool {
+        self.candidates()
+            .chain(other.candidates())
+            .any(|candidate| self.matches(&candidate) && other.matches(&candidate))
+    }
+
+    /// candidates() returns the versions that have to be tried to find out whether another
+    /// requirement intersects with this one. Every comparator of a semver requirement only
+    /// allows versions above a lower bound, and the lowest version two requirements have in
+    /// common is always the greatest of their lower bounds. Those bounds are the version of a
+    /// comparator itself or the next version at the precision of the comparator, like 1.3.0 for
+    /// ">1.2".
+    fn candidates(&self) -> impl Iterator<Item = Version> + '_ {
+        let exact = self.semver.is_none().then(|| Version::parse(&self.raw));
+        let lowest = Some(Version::from(semver::Version::new(0, 0, 0)));
+
+        let bounds = self
+            .semver
+            .iter()
+            .flat_map(|requirement| &requirement.comparators)
+            .flat_map(|comparator| {
+                let major = comparator.major;
+                let minor = comparator.minor.unwrap_or(0);
+                let patch = comparator.patch.unwrap_or(0);
+
+                let mut version = semver::Version::new(major, minor, patch);
+                version.pre = comparator.pre.clone();
+
+                [
+                    version,
+                    semver::Version::new(major, minor, patch + 1),
+                    semver::Version::new(major, minor + 1, 0),
+                    semver::Version::new(major + 1, 0, 0),
+                ]
+            })
+            .map(Version::from);
+
+        exact.into_iter().chain(lowest).chain(bounds)
+    }
+}
+
+/// is_exact_version() returns whether a requirement that isn't a valid semver requirement can be
+/// used as an exact version. Exact versions consist of segments like "2023-01-05" or "1.0rc1"
+/// and contain at least one number.
+fn is_exact_version(requirement: &str) -> bool {
+    requirement.chars().any(|c| c.is_ascii_digit())
+        && requirement
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '~'))
+}
+
+impl Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl TryFrom<String> for VersionRequirement {
+    type Error = Error;
+
+    fn try_from(requirement: String) -> Result<Self, Self::Error> {
+        Self::parse(&requirement)
+    }
+}
+
+impl From<VersionRequirement> for String {
+    fn from(requirement: VersionRequirement) -> Self {
+        requirement.raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(version: &str) -> Version {
+        Version::parse(version)
+    }
+
+    #[test]
+    fn semver_versions_are_compared_with_semver() {
+        assert!(v("1.10.0") > v("1.9.0"));
+        assert!(v("1.0.0") > v("1.0.0-rc.1"));
+        assert!(v("v2.0.0") > v("1.99.99"));
+        assert_eq!(v("v1.2.3"), v("1.2.3"));
+    }
+
+    #[test]
+    fn numeric_segments_are_compared_by_value() {
+        assert!(v("1.10") > v("1.9"));
+        assert!(v("2023.10.1") > v("2023.9.30"));
+        assert!(v("r1234") > v("r999"));
+    }
+
+    #[test]
+    fn additional_numeric_segments_are_newer() {
+        assert!(v("1.0.0.1") > v("1.0.0"));
+        assert!(v("1.2") < v("1.2.1"));
+    }
+
+    #[test]
+    fn textual_segments_mark_pre_releases() {
+        assert!(v("1.0") > v("1.0rc1"));
+        assert!(v("1.0rc2") > v("1.0rc1"));
+        assert!(v("1.0rc1") > v("1.0beta"));
+        assert!(v("1.0.1") > v("1.0rc1"));
+    }
+
+    #[test]
+    fn fallback_ignores_separators_and_prefix() {
+        assert_eq!(v("2023-01-05"), v("2023.01.05"));
+        assert_eq!(v("v1.0_1"), v("1.0.1"));
+        assert_eq!(v("1.0").cmp(&v("1.0")), Ordering::Equal);
+    }
+
+    #[test]
+    fn mixed_versions_use_the_fallback() {
+        assert!(v("1.2.3") < v("1.2.3.1"));
+        assert!(v("1.2.3-beta") < v("1.2.3.1"));
+        assert!(v("1.10.0") > v("1.9"));
+    }
+
+    #[test]
+    fn requirement_parse_accepts_semver_and_exact_versions() {
+        assert!(VersionRequirement::parse(">=1.2, <2").is_ok());
+        assert!(VersionRequirement::parse("2023-01-05").is_ok());
+        assert!(VersionRequirement::parse("1.0rc1").is_ok());
+    }
+
+    #[test]
+    fn requirement_parse_rejects_invalid_requirements() {
+        for requirement in ["", ">=1.2 <2 garbage", "latest", "1.0 || 2.0"] {
+            assert!(
+                matches!(
+                    VersionRequirement::parse(requirement),
+                    Err(Error::InvalidRequirement { .. })
+                ),
+                "{requirement:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn requirement_intersects() {
+        let requirement = |raw| VersionRequirement::parse(raw).unwrap();
+
+        assert!(requirement(">=1.2, <2").intersects(&requirement("~1.5")));
+        assert!(requirement(">1.2").intersects(&requirement("<1.3.1")));
+        assert!(requirement("*").intersects(&requirement("=0.3.1")));
+        assert!(!requirement(">=2").intersects(&requirement("<2")));
+        assert!(!requirement("^1.2").intersects(&requirement("=1.1.9")));
+        assert!(!requirement(">1.2").intersects(&requirement("<1.3.0")));
+        assert!(!requirement("2023-01-05").intersects(&requirement(">=1")));
+    }
+}